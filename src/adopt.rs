@@ -0,0 +1,201 @@
+//! `rauri adopt`: start tracking AUR packages that were installed without rauri, e.g.
+//! with makepkg or another helper, so that updates include them.
+//!
+//! Candidates are the installed packages no sync repository has (like `pacman -Qm`)
+//! that rauri doesn't track yet, looked up in the AUR with one batched query.
+
+use anyhow::{Context, Result};
+use atty::Stream;
+use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+
+use crate::aur::{Aur, AurPackage};
+use crate::config::Config;
+use crate::localdb::{LocalDb, LocalPackage};
+use crate::plan::{Plan, Step};
+use crate::syncdb::SyncDb;
+use crate::tracker::{PackageTracker, TrackedPackage};
+use crate::ui::Ui;
+
+/// An untracked foreign package the AUR has.
+struct Candidate<'a> {
+    local: &'a LocalPackage,
+    aur: &'a AurPackage,
+}
+
+impl Candidate<'_> {
+    fn entry(&self) -> TrackedPackage {
+        TrackedPackage {
+            pkgbase: self.aur.package_base.clone(),
+            version: Some(self.local.version.clone()),
+            commit: None,
+            installed_at: self.local.install_date,
+            updated_at: self.local.install_date,
+            clone_url: Some(Aur::clone_url(&self.aur.package_base)),
+            reason: Some(self.local.reason),
+            pinned: false,
+            ignored: false,
+        }
+    }
+}
+
+/// Untracked foreign packages, split into those the AUR has and those it doesn't.
+fn sort_foreign<'a>(
+    local_db: &'a LocalDb,
+    sync_names: &'a HashSet<String>,
+    tracked: &HashSet<String>,
+    aur_info: &'a HashMap<String, AurPackage>,
+) -> (Vec<Candidate<'a>>, Vec<&'a str>) {
+    let mut found = Vec::new();
+    let mut missing = Vec::new();
+
+    for local in local_db.foreign(sync_names).filter(|p| !tracked.contains(&p.name)) {
+        match aur_info.get(&local.name) {
+            Some(aur) => found.push(Candidate { local, aur }),
+            // Debug packages come with the package they were built with.
+            None if local.name.strip_suffix("-debug").is_some_and(|base| local_db.is_installed(base)) => {}
+            None => missing.push(local.name.as_str()),
+        }
+    }
+
+    (found, missing)
+}
+
+pub struct Adopt;
+
+impl Adopt {
+    /// Track `names`, every candidate with `all`, or the candidates picked interactively.
+    /// Without a terminal and without either, only lists the candidates.
+    pub fn run(names: &[&str], all: bool, config: &Config) -> Result<()> {
+        let local_db = LocalDb::load()?;
+        let sync_db = SyncDb::load_or_warn();
+        let sync_names: HashSet<String> = sync_db.packages().map(|p| p.name.clone()).collect();
+        let tracked = PackageTracker::load().unwrap_or_default();
+
+        let foreign: Vec<&str> = local_db.foreign(&sync_names)
+            .filter(|p| !tracked.contains(&p.name))
+            .map(|p| p.name.as_str())
+            .collect();
+        let aur_info = Aur::get_packages_info(&foreign).context("Failed to query the AUR")?;
+        let (found, missing) = sort_foreign(&local_db, &sync_names, &tracked, &aur_info);
+
+        if !missing.is_empty() {
+            Ui::warning(&format!("Not in the AUR (anymore): {}", missing.join(", ")));
+        }
+
+        let mut failures = 0;
+        let selected: Vec<&Candidate> = if !names.is_empty() {
+            let mut selected = Vec::new();
+            for name in names {
+                let error = match found.iter().find(|c| c.local.name == *name) {
+                    Some(candidate) => {
+                        selected.push(candidate);
+                        continue;
+                    }
+                    None if tracked.contains(*name) => {
+                        Ui::info(&format!("{} is already tracked", name));
+                        continue;
+                    }
+                    None if !local_db.is_installed(name) => "is not installed",
+                    None if sync_names.contains(*name) => "is from the official repositories",
+                    None => "is not in the AUR",
+                };
+                Ui::error(&format!("{} {}", name, error));
+                failures += 1;
+            }
+            selected
+        } else if found.is_empty() {
+            Ui::info("No untracked foreign packages found in the AUR.");
+            return Ok(());
+        } else {
+            Self::print_candidates(&found);
+            if all {
+                found.iter().collect()
+            } else if atty::is(Stream::Stdin) {
+                let answer = Ui::prompt("Packages to track (e.g. \"1 3-5 ^4\"):")?;
+                if answer.is_empty() {
+                    Ui::info("Nothing selected");
+                    return Ok(());
+                }
+                Ui::parse_selection(&answer, found.len())?.into_iter().map(|i| &found[i]).collect()
+            } else {
+                Ui::info("Run 'rauri adopt --all' or name the packages to track them.");
+                return Ok(());
+            }
+        };
+
+        if config.dry_run {
+            let mut plan = Plan::default();
+            plan.extend(selected.iter().map(|c| Step::Track {
+                pkgbase: c.aur.package_base.clone(),
+                pkgnames: vec![c.local.name.clone()],
+            }));
+            plan.print();
+        } else if !selected.is_empty() {
+            PackageTracker::insert(selected.iter().map(|c| (c.local.name.clone(), c.entry())))?;
+            let names: Vec<&str> = selected.iter().map(|c| c.local.name.as_str()).collect();
+            Ui::success(&format!("Now tracking {}", names.join(", ")));
+        }
+
+        if failures > 0 {
+            anyhow::bail!("Failed to adopt {} of {} packages", failures, names.len());
+        }
+        Ok(())
+    }
+
+    fn print_candidates(found: &[Candidate]) {
+        let is_tty = atty::is(Stream::Stdout);
+        for (i, candidate) in found.iter().enumerate() {
+            let mut line = format!("aur/{} {}", candidate.local.name, candidate.local.version);
+            if candidate.aur.version != candidate.local.version {
+                line.push_str(&format!(" (AUR: {})", candidate.aur.version));
+            }
+            if is_tty {
+                println!("{} {}", format!("{:>3}", i + 1).magenta().bold(), line.bold());
+            } else {
+                println!("{:>3} {}", i + 1, line);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::localdb::InstallReason;
+    use std::fs;
+
+    #[test]
+    fn sorts_foreign_packages() {
+        let tmp = tempfile::tempdir().unwrap();
+        for (name, reason) in [("foo", "0"), ("foo-debug", "0"), ("bar", "1"), ("gone", "0"), ("old", "0"), ("glibc", "0")] {
+            let dir = tmp.path().join("local").join(format!("{}-1.0-1", name));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("desc"), format!(
+                "%NAME%\n{}\n\n%VERSION%\n1.0-1\n\n%INSTALLDATE%\n1700000000\n\n%REASON%\n{}\n\n", name, reason)).unwrap();
+        }
+        let local_db = LocalDb::load_from(tmp.path()).unwrap();
+        let sync_names: HashSet<String> = ["glibc".to_string()].into_iter().collect();
+        let tracked: HashSet<String> = ["old".to_string()].into_iter().collect();
+        let aur_info: HashMap<String, AurPackage> = ["foo", "bar"].iter().map(|name| {
+            (name.to_string(), AurPackage {
+                name: name.to_string(),
+                package_base: "foo".to_string(),
+                version: "1.1-1".to_string(),
+                ..AurPackage::default()
+            })
+        }).collect();
+
+        let (found, missing) = sort_foreign(&local_db, &sync_names, &tracked, &aur_info);
+
+        let names: Vec<&str> = found.iter().map(|c| c.local.name.as_str()).collect();
+        assert_eq!(names, vec!["bar", "foo"]);
+        assert_eq!(missing, vec!["gone"]);
+
+        let entry = found[0].entry();
+        assert_eq!(entry.pkgbase, "foo");
+        assert_eq!(entry.version.as_deref(), Some("1.0-1"));
+        assert_eq!(entry.installed_at, Some(1700000000));
+        assert_eq!(entry.reason, Some(InstallReason::Dependency));
+    }
+}
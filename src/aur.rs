@@ -1,19 +1,45 @@
 use anyhow::{Context, Result};
 use once_cell::sync::Lazy;
+use flate2::read::GzDecoder;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+use crate::paths;
+use crate::rollback::BuildCache;
+use crate::runner::Cmd;
+use crate::srcinfo::Srcinfo;
+use crate::ui::Ui;
+
+/// A package as reported by the AUR RPC (v5). Fields only returned by `type=info`
+/// queries (dependencies, licenses, keywords) are empty for search results.
+#[derive(Debug, Clone, Default)]
 pub struct AurPackage {
     pub name: String,
+    pub package_base: String,
     pub version: String,
     pub description: Option<String>,
-    #[allow(dead_code)]
+    pub url: Option<String>,
+    pub maintainer: Option<String>,
     pub votes: i64,
-    #[allow(dead_code)]
     pub popularity: f64,
+    /// Unix timestamp of when the package was flagged out of date, if it is.
+    pub out_of_date: Option<i64>,
+    pub first_submitted: i64,
+    pub last_modified: i64,
+    pub depends: Vec<String>,
+    pub make_depends: Vec<String>,
+    pub check_depends: Vec<String>,
+    pub opt_depends: Vec<String>,
+    pub provides: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub replaces: Vec<String>,
+    pub license: Vec<String>,
+    pub keywords: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,14 +51,71 @@ struct AurSearchResponse {
 struct AurPackageJson {
     #[serde(rename = "Name")]
     name: String,
+    #[serde(rename = "PackageBase", default)]
+    package_base: Option<String>,
     #[serde(rename = "Version")]
     version: String,
     #[serde(rename = "Description")]
     description: Option<String>,
+    #[serde(rename = "URL", default)]
+    url: Option<String>,
+    #[serde(rename = "Maintainer", default)]
+    maintainer: Option<String>,
     #[serde(rename = "NumVotes")]
     num_votes: Option<i64>,
     #[serde(rename = "Popularity")]
     popularity: Option<f64>,
+    #[serde(rename = "OutOfDate", default)]
+    out_of_date: Option<i64>,
+    #[serde(rename = "FirstSubmitted", default)]
+    first_submitted: i64,
+    #[serde(rename = "LastModified", default)]
+    last_modified: i64,
+    #[serde(rename = "Depends", default)]
+    depends: Vec<String>,
+    #[serde(rename = "MakeDepends", default)]
+    make_depends: Vec<String>,
+    #[serde(rename = "CheckDepends", default)]
+    check_depends: Vec<String>,
+    #[serde(rename = "OptDepends", default)]
+    opt_depends: Vec<String>,
+    #[serde(rename = "Provides", default)]
+    provides: Vec<String>,
+    #[serde(rename = "Conflicts", default)]
+    conflicts: Vec<String>,
+    #[serde(rename = "Replaces", default)]
+    replaces: Vec<String>,
+    #[serde(rename = "License", default)]
+    license: Vec<String>,
+    #[serde(rename = "Keywords", default)]
+    keywords: Vec<String>,
+}
+
+impl From<AurPackageJson> for AurPackage {
+    fn from(pkg: AurPackageJson) -> Self {
+        AurPackage {
+            package_base: pkg.package_base.unwrap_or_else(|| pkg.name.clone()),
+            name: pkg.name,
+            version: pkg.version,
+            description: pkg.description,
+            url: pkg.url,
+            maintainer: pkg.maintainer,
+            votes: pkg.num_votes.unwrap_or(0),
+            popularity: pkg.popularity.unwrap_or(0.0),
+            out_of_date: pkg.out_of_date,
+            first_submitted: pkg.first_submitted,
+            last_modified: pkg.last_modified,
+            depends: pkg.depends,
+            make_depends: pkg.make_depends,
+            check_depends: pkg.check_depends,
+            opt_depends: pkg.opt_depends,
+            provides: pkg.provides,
+            conflicts: pkg.conflicts,
+            replaces: pkg.replaces,
+            license: pkg.license,
+            keywords: pkg.keywords,
+        }
+    }
 }
 
 // Reusable HTTP client to avoid creating a new one for each request
@@ -43,12 +126,44 @@ static HTTP_CLIENT: Lazy<reqwest::blocking::Client> = Lazy::new(|| {
         .expect("Failed to create HTTP client")
 });
 
+// The AUR rejects request URLs longer than about 4400 bytes; stay well below that.
+const MAX_INFO_URL_LEN: usize = 4000;
+const INFO_URL: &str = "https://aur.archlinux.org/rpc/?v=5&type=info";
+
+// Plain list of every package name in the AUR, regenerated by the AUR every few minutes.
+const PACKAGES_GZ_URL: &str = "https://aur.archlinux.org/packages.gz";
+// How long the cached name list is used before it is downloaded again.
+const NAME_CACHE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
 // Pre-compiled regex for extracting package names from AUR URLs
 static AUR_URL_REGEX: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"aur\.archlinux\.org/([^/]+)\.git")
         .expect("Failed to compile AUR URL regex")
 });
 
+/// Run git in `dir` and return its trimmed stdout, failing on a non-zero exit.
+pub(crate) fn git_output(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Cmd::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("Failed to execute git")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git {} failed: {}", args.join(" "), stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Package name from an archive name like `foo-docs-1:1.2-3-any.pkg.tar.zst`.
+pub fn pkgname_from_file(file_name: &str) -> Option<&str> {
+    let stem = &file_name[..file_name.find(".pkg.tar")?];
+    // The last three dash-separated fields are pkgver, pkgrel and arch.
+    stem.rsplitn(4, '-').nth(3)
+}
+
 pub struct Aur;
 
 impl Aur {
@@ -64,74 +179,182 @@ impl Aur {
         url.contains("aur.archlinux.org") && url.ends_with(".git")
     }
 
+    /// Clone the AUR repository at `url` into `download_dir`, or bring an existing
+    /// checkout up to date so built packages, downloaded sources and history survive.
     pub fn clone_repo(url: &str, download_dir: &Path) -> Result<PathBuf> {
         let package_name = Self::extract_package_name(url)?;
         let target_dir = download_dir.join(&package_name);
-        
-        // Remove existing directory if it exists
+        Self::sync_checkout(url, &target_dir)?;
+        Ok(target_dir)
+    }
+
+    /// Fetch and reset an existing checkout of `url` in `target_dir` to the remote, or
+    /// clone it if the directory is missing or isn't a usable checkout of `url`.
+    /// Fails without touching anything if tracked files were modified locally, apart
+    /// from the `pkgver=` makepkg writes into the PKGBUILD of VCS packages.
+    fn sync_checkout(url: &str, target_dir: &Path) -> Result<()> {
         if target_dir.exists() {
-            std::fs::remove_dir_all(&target_dir)
-                .with_context(|| format!("Failed to remove existing directory: {}", target_dir.display()))?;
+            match Self::checkout_problem(url, target_dir) {
+                None => return Self::fetch_and_reset(target_dir),
+                Some(problem) => {
+                    Ui::warning(&format!("{} ({}), cloning again", problem, target_dir.display()));
+                    std::fs::remove_dir_all(target_dir)
+                        .with_context(|| format!("Failed to remove existing directory: {}", target_dir.display()))?;
+                }
+            }
         }
-        
-        let output = Command::new("git")
+
+        let output = Cmd::new("git")
             .arg("clone")
             .arg(url)
-            .arg(&target_dir)
+            .arg(target_dir)
             .output()
             .context("Failed to execute git clone")?;
-        
+
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             anyhow::bail!("Git clone failed: {}", stderr);
         }
-        
-        Ok(target_dir)
+
+        Ok(())
+    }
+
+    /// Why `target_dir` can't be updated in place, or None if it can.
+    fn checkout_problem(url: &str, target_dir: &Path) -> Option<&'static str> {
+        let toplevel = match git_output(target_dir, &["rev-parse", "--show-toplevel"]) {
+            Ok(t) => PathBuf::from(t),
+            Err(_) => return Some("Not a git checkout"),
+        };
+        // A directory without its own .git would otherwise resolve to a parent repository.
+        if toplevel.canonicalize().ok() != target_dir.canonicalize().ok() {
+            return Some("Not a git checkout");
+        }
+        if git_output(target_dir, &["rev-parse", "--verify", "HEAD"]).is_err() {
+            return Some("Checkout is corrupt");
+        }
+        match git_output(target_dir, &["remote", "get-url", "origin"]) {
+            Ok(origin) if origin == url => None,
+            _ => Some("Checkout has a different remote"),
+        }
+    }
+
+    fn fetch_and_reset(target_dir: &Path) -> Result<()> {
+        let modified = git_output(target_dir, &["diff", "--name-only", "HEAD"])?;
+        let mut files: Vec<&str> = modified.lines().collect();
+        // makepkg rewrites pkgver= in the PKGBUILD of VCS packages on every build.
+        if files.contains(&"PKGBUILD") && Self::only_pkgver_changed(target_dir)? {
+            git_output(target_dir, &["checkout", "HEAD", "--", "PKGBUILD"])?;
+            files.retain(|f| *f != "PKGBUILD");
+        }
+        if !files.is_empty() {
+            anyhow::bail!(
+                "Local modifications in {}: {}. Commit or discard them, or delete the directory.",
+                target_dir.display(), files.join(", ")
+            );
+        }
+
+        git_output(target_dir, &["fetch", "--quiet", "origin"])?;
+        // AUR repositories only have a master branch, but follow origin/HEAD if it is set.
+        let remote_head = git_output(target_dir, &["rev-parse", "--verify", "--quiet", "origin/HEAD"])
+            .unwrap_or_else(|_| "origin/master".to_string());
+        git_output(target_dir, &["reset", "--hard", "--quiet", &remote_head])?;
+
+        Ok(())
+    }
+
+    /// Whether the only local change to the PKGBUILD is to its `pkgver=` line.
+    fn only_pkgver_changed(target_dir: &Path) -> Result<bool> {
+        let diff = git_output(target_dir, &["diff", "-U0", "HEAD", "--", "PKGBUILD"])?;
+        let mut changed = diff.lines()
+            .filter(|l| (l.starts_with('+') || l.starts_with('-')) && !l.starts_with("+++") && !l.starts_with("---"))
+            .peekable();
+        Ok(changed.peek().is_some() && changed.all(|l| l[1..].starts_with("pkgver=")))
     }
 
-    pub fn build_and_install(package_dir: &Path, requested_package: &str) -> Result<String> {
-        let output = Command::new("makepkg")
-            .arg("-si")
+    /// Git URL of the AUR repository for `pkgbase`. Split packages share the
+    /// repository of their pkgbase.
+    pub fn clone_url(pkgbase: &str) -> String {
+        format!("https://aur.archlinux.org/{}.git", pkgbase)
+    }
+
+    /// Build the pkgbase in `package_dir` and install the given pkgnames from it,
+    /// returning the version that was installed. The archives are copied to the build
+    /// cache, which keeps the last `keep_builds` of each package.
+    /// Pass as_dep=true to mark them as installed as dependencies of another package.
+    pub fn build_and_install(package_dir: &Path, pkgnames: &[String], as_dep: bool, keep_builds: usize) -> Result<String> {
+        let srcinfo = Srcinfo::from_dir(package_dir)?;
+        if let Some(unknown) = pkgnames.iter().find(|n| srcinfo.package(n).is_none()) {
+            anyhow::bail!("{} is not built by {}", unknown, srcinfo.base.pkgbase);
+        }
+
+        let all: Vec<&str> = srcinfo.pkgnames().collect();
+        if all.len() > 1 || all[0] != srcinfo.base.pkgbase {
+            Ui::info(&format!("Building {} {} ({})",
+                srcinfo.base.pkgbase, srcinfo.version(), all.join(", ")));
+        }
+
+        let status = Cmd::new("makepkg")
+            .arg("-sf")
             .current_dir(package_dir)
             .status()
             .context("Failed to execute makepkg")?;
-        
-        if !output.success() {
-            anyhow::bail!("makepkg -si failed");
+
+        if !status.success() {
+            anyhow::bail!("makepkg failed for {}", srcinfo.base.pkgbase);
         }
-        
-        // Find what package was actually installed
-        let mut actual_package_name = requested_package.to_string();
-        
-        // Look for .pkg.tar.zst files
-        if let Ok(entries) = std::fs::read_dir(package_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.extension().and_then(|s| s.to_str()) == Some("zst") {
-                    if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                        if file_name.ends_with(".pkg.tar.zst") {
-                            // Get package name from the built package
-                            let output = Command::new("pacman")
-                                .arg("-Qp")
-                                .arg(&path)
-                                .output();
-                            
-                            if let Ok(output) = output {
-                                if output.status.success() {
-                                    let stdout = String::from_utf8_lossy(&output.stdout);
-                                    if let Some(name) = stdout.trim().split_whitespace().next() {
-                                        actual_package_name = name.to_string();
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+
+        let files: Vec<PathBuf> = Self::package_files(package_dir)?
+            .into_iter()
+            .filter(|f| {
+                f.file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(pkgname_from_file)
+                    .is_some_and(|name| pkgnames.iter().any(|p| p == name))
+            })
+            .collect();
+
+        if files.len() != pkgnames.len() {
+            anyhow::bail!("makepkg did not produce packages for all of: {}", pkgnames.join(", "));
         }
-        
-        Ok(actual_package_name)
+
+        let mut pacman = Cmd::new("sudo").arg("pacman").arg("-U");
+        if as_dep {
+            pacman = pacman.arg("--asdeps");
+        }
+        let status = pacman.args(&files)
+            .status()
+            .context("Failed to execute pacman -U")?;
+
+        if !status.success() {
+            anyhow::bail!("Failed to install {}", pkgnames.join(", "));
+        }
+
+        BuildCache::store(&files, keep_builds);
+        Ok(srcinfo.version())
+    }
+
+    /// The commit checked out in `package_dir`, if it is a git checkout.
+    pub fn head_commit(package_dir: &Path) -> Option<String> {
+        git_output(package_dir, &["rev-parse", "HEAD"]).ok().filter(|c| !c.is_empty())
+    }
+
+    /// Paths of the package archives makepkg builds for `package_dir`.
+    fn package_files(package_dir: &Path) -> Result<Vec<PathBuf>> {
+        let output = Cmd::new("makepkg")
+            .arg("--packagelist")
+            .current_dir(package_dir)
+            .output()
+            .context("Failed to execute makepkg --packagelist")?;
+
+        if !output.status.success() {
+            anyhow::bail!("makepkg --packagelist failed");
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| package_dir.join(l.trim()))
+            .collect())
     }
 
     pub fn search(query: &str) -> Result<Vec<AurPackage>> {
@@ -145,41 +368,364 @@ impl Aur {
         let json_data: AurSearchResponse = response.json()
             .context("Failed to parse search response")?;
         
-        let packages: Vec<AurPackage> = json_data.results.into_iter().map(|pkg| {
-            AurPackage {
-                name: pkg.name,
-                version: pkg.version,
-                description: pkg.description,
-                votes: pkg.num_votes.unwrap_or(0),
-                popularity: pkg.popularity.unwrap_or(0.0),
-            }
-        }).collect();
+        let packages: Vec<AurPackage> = json_data.results.into_iter()
+            .map(AurPackage::from)
+            .collect();
         
         Ok(packages)
     }
 
-    pub fn get_package_info(package_name: &str) -> Result<AurPackage> {
-        let url = format!("https://aur.archlinux.org/rpc/?v=5&type=info&arg={}", 
-                         urlencoding::encode(package_name));
-        
+    /// Names of the AUR packages that provide `name`, including one called `name`.
+    pub fn providers(name: &str) -> Result<Vec<String>> {
+        let url = format!("https://aur.archlinux.org/rpc/?v=5&type=search&by=provides&arg={}",
+                         urlencoding::encode(name));
+
         let response = HTTP_CLIENT.get(&url)
+            .send()
+            .context("Failed to send search request")?;
+
+        let json_data: AurSearchResponse = response.json()
+            .context("Failed to parse search response")?;
+
+        Ok(json_data.results.into_iter().map(|pkg| pkg.name).collect())
+    }
+
+    /// Look up many packages at once using the RPC's `arg[]` form. Requests are split
+    /// into chunks to keep URLs under the AUR's length limit. Packages that don't exist
+    /// in the AUR are simply absent from the returned map.
+    pub fn get_packages_info(package_names: &[&str]) -> Result<HashMap<String, AurPackage>> {
+        let mut packages = HashMap::new();
+        for url in Self::info_urls(package_names) {
+            Self::fetch_info_chunk(&url, &mut packages)?;
+        }
+        Ok(packages)
+    }
+
+    /// The info request URLs for `package_names`, each at most `MAX_INFO_URL_LEN` long
+    /// unless a single name is longer than that.
+    fn info_urls(package_names: &[&str]) -> Vec<String> {
+        let mut urls = Vec::new();
+        let mut url = String::from(INFO_URL);
+        let mut args_in_url = 0;
+
+        for name in package_names {
+            let arg = format!("&arg[]={}", urlencoding::encode(name));
+            if args_in_url > 0 && url.len() + arg.len() > MAX_INFO_URL_LEN {
+                urls.push(std::mem::replace(&mut url, String::from(INFO_URL)));
+                args_in_url = 0;
+            }
+            url.push_str(&arg);
+            args_in_url += 1;
+        }
+
+        if args_in_url > 0 {
+            urls.push(url);
+        }
+        urls
+    }
+
+    pub fn name_cache_path() -> PathBuf {
+        paths::cache_dir().join("packages.txt")
+    }
+
+    /// Download the AUR's packages.gz for completion if the cached copy is older than a
+    /// day. A failed download only warns; the outdated copy is still used.
+    pub fn refresh_package_names() {
+        let path = Self::name_cache_path();
+        let fresh = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age < NAME_CACHE_MAX_AGE);
+
+        if !fresh {
+            if let Err(e) = Self::download_package_names(&path) {
+                Ui::warning(&format!("Failed to refresh the AUR package list: {:#}", e));
+            }
+        }
+    }
+
+    /// Names of all AUR packages from the cached list, however old. Never downloads,
+    /// so completion stays fast offline.
+    pub fn cached_package_names() -> Result<Vec<String>> {
+        let path = Self::name_cache_path();
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(content.lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_string())
+            .collect())
+    }
+
+    fn download_package_names(path: &Path) -> Result<()> {
+        let bytes = HTTP_CLIENT.get(PACKAGES_GZ_URL)
+            .send()
+            .and_then(|r| r.error_for_status())
+            .context("Failed to download the AUR package list")?
+            .bytes()
+            .context("Failed to download the AUR package list")?;
+
+        let mut names = String::new();
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            GzDecoder::new(&bytes[..]).read_to_string(&mut names)
+                .context("Failed to decompress the AUR package list")?;
+        } else {
+            names = String::from_utf8_lossy(&bytes).to_string();
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create cache directory: {}", parent.display()))?;
+        }
+        // Write to a temporary file first so concurrent completions never see half a list.
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, names)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        Ok(())
+    }
+
+    fn fetch_info_chunk(url: &str, packages: &mut HashMap<String, AurPackage>) -> Result<()> {
+        let response = HTTP_CLIENT.get(url)
             .send()
             .context("Failed to send info request")?;
-        
+
         let json_data: AurSearchResponse = response.json()
             .context("Failed to parse info response")?;
-        
-        if let Some(pkg) = json_data.results.first() {
-            Ok(AurPackage {
-                name: pkg.name.clone(),
-                version: pkg.version.clone(),
-                description: pkg.description.clone(),
-                votes: pkg.num_votes.unwrap_or(0),
-                popularity: pkg.popularity.unwrap_or(0.0),
-            })
-        } else {
-            anyhow::bail!("Package not found: {}", package_name)
+
+        for pkg in json_data.results {
+            packages.insert(pkg.name.clone(), AurPackage::from(pkg));
         }
+
+        Ok(())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, FakeRunner};
+    use std::fs;
+    use std::process::Command;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(["-c", "user.name=rauri", "-c", "user.email=rauri@localhost"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .expect("failed to run git");
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    /// A bare "AUR" repository plus a work tree used to push new commits to it.
+    fn remote(root: &Path) -> (String, PathBuf) {
+        let upstream = root.join("foo.git");
+        let work = root.join("work");
+        fs::create_dir_all(&upstream).unwrap();
+        fs::create_dir_all(&work).unwrap();
+
+        git(&upstream, &["init", "--bare", "-b", "master"]);
+        git(&work, &["init", "-b", "master"]);
+        commit(&work, "pkgver=1");
+        git(&work, &["remote", "add", "origin", upstream.to_str().unwrap()]);
+        git(&work, &["push", "origin", "master"]);
+
+        (upstream.to_str().unwrap().to_string(), work)
+    }
+
+    fn commit(work: &Path, pkgbuild: &str) {
+        fs::write(work.join("PKGBUILD"), pkgbuild).unwrap();
+        git(work, &["add", "PKGBUILD"]);
+        git(work, &["commit", "-m", pkgbuild]);
+    }
+
+    #[test]
+    fn parses_info_response() {
+        // Trimmed from a real v5 info response: an orphaned package has a null
+        // Maintainer, and the dependency arrays are left out when empty.
+        let json = r#"{
+            "resultcount": 2,
+            "results": [
+                {
+                    "Description": "Foo from git",
+                    "FirstSubmitted": 1500000000,
+                    "ID": 1234567,
+                    "Keywords": ["foo", "vcs"],
+                    "LastModified": 1714521600,
+                    "License": ["MIT"],
+                    "Maintainer": null,
+                    "MakeDepends": ["git"],
+                    "Name": "foo-git",
+                    "NumVotes": 12,
+                    "OutOfDate": null,
+                    "PackageBase": "foo-git",
+                    "PackageBaseID": 98765,
+                    "Popularity": 0.251234,
+                    "Provides": ["foo"],
+                    "Conflicts": ["foo"],
+                    "Submitter": "someone",
+                    "URL": "https://example.org/foo",
+                    "URLPath": "/cgit/aur.git/snapshot/foo-git.tar.gz",
+                    "Version": "1.0.r3.gabc1234-1"
+                },
+                {
+                    "Depends": ["foo-git", "zlib>=1.3"],
+                    "Description": null,
+                    "FirstSubmitted": 1600000000,
+                    "ID": 1234568,
+                    "LastModified": 1600000000,
+                    "Maintainer": "someone",
+                    "Name": "foo-docs",
+                    "NumVotes": 0,
+                    "OutOfDate": 1700000000,
+                    "PackageBase": "foo-docs",
+                    "PackageBaseID": 98766,
+                    "Popularity": 0,
+                    "URL": null,
+                    "URLPath": "/cgit/aur.git/snapshot/foo-docs.tar.gz",
+                    "Version": "1.0-1"
+                }
+            ],
+            "type": "multiinfo",
+            "version": 5
+        }"#;
+
+        let response: AurSearchResponse = serde_json::from_str(json).unwrap();
+        let packages: Vec<AurPackage> = response.results.into_iter().map(AurPackage::from).collect();
+
+        let foo = &packages[0];
+        assert_eq!((foo.name.as_str(), foo.version.as_str()), ("foo-git", "1.0.r3.gabc1234-1"));
+        assert_eq!(foo.maintainer, None);
+        assert_eq!(foo.out_of_date, None);
+        assert_eq!((foo.votes, foo.popularity), (12, 0.251234));
+        assert_eq!(foo.make_depends, vec!["git"]);
+        assert!(foo.depends.is_empty() && foo.opt_depends.is_empty());
+        assert_eq!(foo.keywords, vec!["foo", "vcs"]);
+
+        let docs = &packages[1];
+        assert_eq!(docs.description, None);
+        assert_eq!(docs.url, None);
+        assert_eq!(docs.maintainer.as_deref(), Some("someone"));
+        assert_eq!(docs.out_of_date, Some(1700000000));
+        assert_eq!(docs.depends, vec!["foo-git", "zlib>=1.3"]);
+        assert!(docs.license.is_empty() && docs.provides.is_empty());
+    }
+
+    #[test]
+    fn splits_info_requests_into_short_urls() {
+        assert!(Aur::info_urls(&[]).is_empty());
+        assert_eq!(Aur::info_urls(&["foo", "libc++", "a b"]),
+            vec![format!("{}&arg[]=foo&arg[]=libc%2B%2B&arg[]=a%20b", INFO_URL)]);
+
+        let names: Vec<String> = (0..1000).map(|i| format!("package-{:04}", i)).collect();
+        let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+        let urls = Aur::info_urls(&names);
+        assert!(urls.len() > 1);
+        assert!(urls.iter().all(|url| url.len() <= MAX_INFO_URL_LEN && url.starts_with(INFO_URL)));
+
+        let args: Vec<&str> = urls.iter().flat_map(|url| url.split("&arg[]=").skip(1)).collect();
+        assert_eq!(args, names);
+    }
+
+    #[test]
+    fn updates_existing_checkout_in_place() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (url, work) = remote(tmp.path());
+        let checkout = tmp.path().join("aur").join("foo");
+
+        Aur::sync_checkout(&url, &checkout).unwrap();
+        fs::write(checkout.join("foo-1-1-x86_64.pkg.tar.zst"), "built").unwrap();
+
+        commit(&work, "pkgver=2");
+        git(&work, &["push", "origin", "master"]);
+        Aur::sync_checkout(&url, &checkout).unwrap();
+
+        assert_eq!(fs::read_to_string(checkout.join("PKGBUILD")).unwrap(), "pkgver=2");
+        assert!(checkout.join("foo-1-1-x86_64.pkg.tar.zst").exists());
+        assert_eq!(git(&checkout, &["rev-list", "--count", "HEAD"]), "2");
+    }
+
+    #[test]
+    fn refuses_to_discard_local_modifications() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (url, _) = remote(tmp.path());
+        let checkout = tmp.path().join("foo");
+
+        Aur::sync_checkout(&url, &checkout).unwrap();
+        fs::write(checkout.join("PKGBUILD"), "pkgver=local\nsource=(local.patch)").unwrap();
+
+        assert!(Aur::sync_checkout(&url, &checkout).is_err());
+        assert_eq!(fs::read_to_string(checkout.join("PKGBUILD")).unwrap(), "pkgver=local\nsource=(local.patch)");
+
+        // A pkgver rewritten by makepkg is not a local modification.
+        fs::write(checkout.join("PKGBUILD"), "pkgver=r2.abc").unwrap();
+        Aur::sync_checkout(&url, &checkout).unwrap();
+        assert_eq!(fs::read_to_string(checkout.join("PKGBUILD")).unwrap(), "pkgver=1");
+    }
+
+    #[test]
+    fn discards_pkgver_rewritten_by_makepkg() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("foo-git");
+        fs::create_dir_all(&dir).unwrap();
+        let url = Aur::clone_url("foo-git");
+        let fake = FakeRunner::new();
+        let _runner = runner::install(fake.clone());
+        fake.on("git rev-parse --show-toplevel", 0, &dir.display().to_string());
+        fake.on("git remote get-url origin", 0, &url);
+        fake.on("git diff --name-only HEAD", 0, "PKGBUILD\n");
+        fake.on("git diff -U0 HEAD -- PKGBUILD", 0, "diff --git a/PKGBUILD b/PKGBUILD\n\
+            --- a/PKGBUILD\n\
+            +++ b/PKGBUILD\n\
+            @@ -3 +3 @@\n\
+            -pkgver=r10.abc1234\n\
+            +pkgver=r12.def5678\n");
+        fake.on("git rev-parse --verify --quiet origin/HEAD", 1, "");
+
+        Aur::sync_checkout(&url, &dir).unwrap();
+        let calls = fake.calls();
+        assert_eq!(calls[4..], [
+            "git diff -U0 HEAD -- PKGBUILD",
+            "git checkout HEAD -- PKGBUILD",
+            "git fetch --quiet origin",
+            "git rev-parse --verify --quiet origin/HEAD",
+            "git reset --hard --quiet origin/master",
+        ]);
+
+        // Anything beyond pkgver= is a real local change.
+        let fake = FakeRunner::new();
+        let _runner = runner::install(fake.clone());
+        fake.on("git rev-parse --show-toplevel", 0, &dir.display().to_string());
+        fake.on("git remote get-url origin", 0, &url);
+        fake.on("git diff --name-only HEAD", 0, "PKGBUILD\n");
+        fake.on("git diff -U0 HEAD -- PKGBUILD", 0, "@@ -3 +3,2 @@\n-pkgver=1\n+pkgver=2\n+source=(evil)\n");
+
+        let error = Aur::sync_checkout(&url, &dir).unwrap_err();
+        assert!(error.to_string().starts_with("Local modifications in"));
+        assert!(!fake.calls().iter().any(|call| call.starts_with("git checkout") || call.starts_with("git reset")));
+    }
+
+    #[test]
+    fn reclones_broken_checkout() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (url, _) = remote(tmp.path());
+        let checkout = tmp.path().join("foo");
+
+        fs::create_dir_all(&checkout).unwrap();
+        fs::write(checkout.join("PKGBUILD"), "not a checkout").unwrap();
+
+        Aur::sync_checkout(&url, &checkout).unwrap();
+        assert_eq!(fs::read_to_string(checkout.join("PKGBUILD")).unwrap(), "pkgver=1");
+    }
+
+    #[test]
+    fn pkgname_from_archive_name() {
+        assert_eq!(pkgname_from_file("foo-docs-1:1.2-3-any.pkg.tar.zst"), Some("foo-docs"));
+        assert_eq!(pkgname_from_file("foo-1.0-1-x86_64.pkg.tar.xz"), Some("foo"));
+        assert_eq!(pkgname_from_file("PKGBUILD"), None);
+    }
+}
@@ -0,0 +1,749 @@
+//! Command line parsing and the generated `--help` text.
+//!
+//! Commands are matched by their configured short handle (`cmd_search`, `cmd_install`,
+//! ... in the config) or their fixed long form, so the table below is the single
+//! description of what rauri accepts and what `--help` shows.
+
+use anyhow::Result;
+use atty::Stream;
+use colored::Colorize;
+use std::path::PathBuf;
+
+use crate::aur::Aur;
+use crate::config::Config;
+use crate::history::Filter;
+use crate::ui::Ui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandKind {
+    Search,
+    SearchAll,
+    Install,
+    Info,
+    UpdateAur,
+    UpdateAll,
+    CheckUpdates,
+    Remove,
+    List,
+    ListAll,
+    UpdateMirrors,
+    Adopt,
+    Export,
+    Import,
+    History,
+    Rollback,
+    Completions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// What the arguments of a command are, so shell completion can offer candidates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completion {
+    Nothing,
+    /// Official and AUR package names.
+    Installable,
+    /// Installed foreign packages.
+    Installed,
+    Files,
+    Shells,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Search { query: String, all: bool },
+    Install { targets: Vec<String>, needed: bool },
+    Info { targets: Vec<String> },
+    UpdateAur,
+    UpdateAll { skip_aur: bool, needed: bool },
+    CheckUpdates,
+    Remove { targets: Vec<String> },
+    List { all: bool },
+    UpdateMirrors,
+    Adopt { targets: Vec<String>, all: bool },
+    /// Write the manifest to `path`, or to stdout.
+    Export { path: Option<PathBuf>, versions: bool },
+    Import { path: PathBuf, diff: bool },
+    History(Filter),
+    /// Reinstall a cached build: `version`, or the one before the installed version.
+    Rollback { package: String, version: Option<String> },
+    Completions(Shell),
+    /// Print the package names shell completion offers for `what`, starting with `prefix`.
+    CompletionNames { what: Completion, prefix: String },
+    /// A bare search term: search, then pick results to install.
+    SearchAndInstall(String),
+    AurUrl(String),
+    /// General help, or the help of one command.
+    Help(Option<CommandKind>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cli {
+    /// -C: clear the AUR download directory before running the command.
+    pub clear_aur_path: bool,
+    pub devel: bool,
+    pub dry_run: bool,
+    /// --json: machine-readable output, with messages as JSON objects on stderr.
+    pub json: bool,
+    /// -P <path>: set the AUR download directory and exit.
+    pub set_path: Option<PathBuf>,
+    pub command: Command,
+}
+
+struct OptionSpec {
+    long: &'static str,
+    short: Option<&'static str>,
+    /// Name of the value the option takes, if any.
+    value: Option<&'static str>,
+    help: &'static str,
+}
+
+struct CommandSpec {
+    kind: CommandKind,
+    matches: fn(&Config, &str) -> bool,
+    /// Handles shown in help, e.g. "-Q, --search".
+    handles: fn(&Config) -> String,
+    args: &'static str,
+    min_args: usize,
+    max_args: Option<usize>,
+    options: &'static [OptionSpec],
+    completes: Completion,
+    /// `{limit}` is replaced with the configured search limit.
+    about: &'static str,
+}
+
+const GLOBAL_OPTIONS: &[OptionSpec] = &[
+    OptionSpec { long: "--clear", short: Some("-C"), value: None,
+        help: "Clear AUR download directory before command" },
+    OptionSpec { long: "--path", short: Some("-P"), value: Some("<path>"),
+        help: "Set AUR download directory path" },
+    OptionSpec { long: "--devel", short: None, value: None,
+        help: "Also rebuild -git/-svn/... packages with new upstream commits" },
+    OptionSpec { long: "--dry-run", short: None, value: None,
+        help: "Print what install, update, remove and clear would do without doing it" },
+    OptionSpec { long: "--json", short: None, value: None,
+        help: "Print search, list and info results as JSON" },
+    OptionSpec { long: "--help", short: Some("-h"), value: None,
+        help: "Show help, or the help of a command" },
+];
+
+const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        kind: CommandKind::Search,
+        matches: Config::is_search_cmd,
+        handles: |c| format!("{}, --search", c.cmd_search),
+        args: "<query>", min_args: 1, max_args: Some(1),
+        options: &[],
+        completes: Completion::Nothing,
+        about: "Search packages (top {limit} per repo)",
+    },
+    CommandSpec {
+        kind: CommandKind::SearchAll,
+        matches: Config::is_search_all_cmd,
+        handles: |c| format!("{}A, --search-all", c.cmd_search),
+        args: "<query>", min_args: 1, max_args: Some(1),
+        options: &[],
+        completes: Completion::Nothing,
+        about: "Search packages (show all results)",
+    },
+    CommandSpec {
+        kind: CommandKind::Install,
+        matches: Config::is_install_cmd,
+        handles: |c| format!("{}, --install", c.cmd_install),
+        args: "<pkg>...", min_args: 0, max_args: None,
+        options: &[
+            OptionSpec { long: "--needed", short: None, value: None,
+                help: "Skip targets that are already installed and up to date" },
+        ],
+        completes: Completion::Installable,
+        about: "Install packages (AUR or official)",
+    },
+    CommandSpec {
+        kind: CommandKind::Info,
+        matches: Config::is_info_cmd,
+        handles: |c| format!("{}, --info", c.cmd_info),
+        args: "<pkg>...", min_args: 1, max_args: None,
+        options: &[],
+        completes: Completion::Installable,
+        about: "Show package details (AUR or official)",
+    },
+    CommandSpec {
+        kind: CommandKind::UpdateAur,
+        matches: |_, cmd| cmd == "--update-aur",
+        handles: |c| format!("{}, --update-aur", c.cmd_install),
+        args: "", min_args: 0, max_args: Some(0),
+        options: &[],
+        completes: Completion::Nothing,
+        about: "Update AUR packages only",
+    },
+    CommandSpec {
+        kind: CommandKind::UpdateAll,
+        matches: Config::is_update_all_cmd,
+        handles: |c| format!("{}, --update-all", c.cmd_update_all),
+        args: "", min_args: 0, max_args: Some(0),
+        options: &[
+            OptionSpec { long: "--skip-aur", short: None, value: None,
+                help: "Only update official packages" },
+            OptionSpec { long: "--needed", short: None, value: None,
+                help: "Passed on to pacman -Syu" },
+        ],
+        completes: Completion::Nothing,
+        about: "Update whole system (pacman -Syy then -Syu, then AUR)",
+    },
+    CommandSpec {
+        kind: CommandKind::CheckUpdates,
+        matches: Config::is_check_updates_cmd,
+        handles: |c| format!("{}, --check-updates", c.cmd_check_updates),
+        args: "", min_args: 0, max_args: Some(0),
+        options: &[],
+        completes: Completion::Nothing,
+        about: "List pending updates without installing (exit 0: updates, 2: none, 1: error)",
+    },
+    CommandSpec {
+        kind: CommandKind::UpdateMirrors,
+        matches: Config::is_update_mirrors_cmd,
+        handles: |c| format!("{}, --update-mirrors", c.cmd_update_mirrors),
+        args: "", min_args: 0, max_args: Some(0),
+        options: &[],
+        completes: Completion::Nothing,
+        about: "Update mirrorlist with reflector",
+    },
+    CommandSpec {
+        kind: CommandKind::Remove,
+        matches: Config::is_remove_cmd,
+        handles: |c| format!("{}, --remove", c.cmd_remove),
+        args: "<pkg>...", min_args: 1, max_args: None,
+        options: &[],
+        completes: Completion::Installed,
+        about: "Remove packages (also removes package folders)",
+    },
+    CommandSpec {
+        kind: CommandKind::List,
+        matches: Config::is_list_cmd,
+        handles: |c| format!("{}, --list", c.cmd_list),
+        args: "", min_args: 0, max_args: Some(0),
+        options: &[],
+        completes: Completion::Nothing,
+        about: "List AUR packages installed via rauri",
+    },
+    CommandSpec {
+        kind: CommandKind::ListAll,
+        matches: Config::is_list_all_cmd,
+        handles: |c| format!("{}A, --list-all", c.cmd_list),
+        args: "", min_args: 0, max_args: Some(0),
+        options: &[],
+        completes: Completion::Nothing,
+        about: "List all installed system packages",
+    },
+    CommandSpec {
+        kind: CommandKind::Adopt,
+        matches: |_, cmd| cmd == "adopt",
+        handles: |_| "adopt".to_string(),
+        args: "[pkg]...", min_args: 0, max_args: None,
+        options: &[
+            OptionSpec { long: "--all", short: None, value: None,
+                help: "Track every foreign package found in the AUR" },
+        ],
+        completes: Completion::Installed,
+        about: "Track AUR packages installed without rauri, so updates include them",
+    },
+    CommandSpec {
+        kind: CommandKind::Export,
+        matches: |_, cmd| cmd == "export",
+        handles: |_| "export".to_string(),
+        args: "[file]", min_args: 0, max_args: Some(1),
+        options: &[
+            OptionSpec { long: "--versions", short: None, value: None,
+                help: "Record installed versions and AUR commits" },
+        ],
+        completes: Completion::Files,
+        about: "Write a manifest of explicitly installed official and AUR packages",
+    },
+    CommandSpec {
+        kind: CommandKind::Import,
+        matches: |_, cmd| cmd == "import",
+        handles: |_| "import".to_string(),
+        args: "<file>", min_args: 1, max_args: Some(1),
+        options: &[
+            OptionSpec { long: "--diff", short: None, value: None,
+                help: "Only show what would be added or removed" },
+        ],
+        completes: Completion::Files,
+        about: "Install the packages of a manifest that are missing",
+    },
+    CommandSpec {
+        kind: CommandKind::History,
+        matches: |_, cmd| cmd == "history",
+        handles: |_| "history".to_string(),
+        args: "[pkg]", min_args: 0, max_args: Some(1),
+        options: &[
+            OptionSpec { long: "--since", short: None, value: Some("<YYYY-MM-DD>"),
+                help: "Only show events from this day on" },
+            OptionSpec { long: "--until", short: None, value: Some("<YYYY-MM-DD>"),
+                help: "Only show events up to and including this day" },
+            OptionSpec { long: "--result", short: None, value: Some("<ok|failed>"),
+                help: "Only show successful or failed events" },
+        ],
+        completes: Completion::Nothing,
+        about: "Show recorded installs, updates, removals and clears",
+    },
+    CommandSpec {
+        kind: CommandKind::Rollback,
+        matches: |_, cmd| cmd == "rollback",
+        handles: |_| "rollback".to_string(),
+        args: "<pkg> [version]", min_args: 1, max_args: Some(2),
+        options: &[],
+        completes: Completion::Installed,
+        about: "Reinstall an earlier cached build of an AUR package",
+    },
+    CommandSpec {
+        kind: CommandKind::Completions,
+        matches: |_, cmd| cmd == "completions",
+        handles: |_| "completions".to_string(),
+        args: "<bash|zsh|fish>", min_args: 0, max_args: Some(1),
+        options: &[
+            OptionSpec { long: "--list", short: None, value: Some("<install|remove>"),
+                help: "Print the package names completed for install or remove" },
+        ],
+        completes: Completion::Shells,
+        about: "Print a shell completion script",
+    },
+];
+
+fn spec(kind: CommandKind) -> &'static CommandSpec {
+    COMMANDS.iter().find(|s| s.kind == kind).expect("every command has a spec")
+}
+
+fn find_option<'a>(options: &'a [OptionSpec], name: &str) -> Option<&'a OptionSpec> {
+    options.iter().find(|o| o.long == name || o.short == Some(name))
+}
+
+impl Cli {
+    /// Parse the arguments after the program name.
+    pub fn parse(args: &[String], config: &Config) -> Result<Self> {
+        let mut cli = Cli {
+            clear_aur_path: false,
+            devel: false,
+            dry_run: false,
+            json: false,
+            set_path: None,
+            command: Command::Help(None),
+        };
+
+        let mut kind: Option<CommandKind> = None;
+        let mut bare: Option<String> = None;
+        let mut positional: Vec<String> = Vec::new();
+        // Options given to the command: (long name, value).
+        let mut options: Vec<(&'static str, Option<String>)> = Vec::new();
+        let mut help = false;
+        let mut only_positional = false;
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if kind.is_none() && bare.is_none() && !only_positional {
+                if let Some(spec) = COMMANDS.iter().find(|s| (s.matches)(config, arg)) {
+                    kind = Some(spec.kind);
+                    continue;
+                }
+            }
+
+            if only_positional || !arg.starts_with('-') || arg == "-" {
+                if kind.is_none() && bare.is_none() {
+                    bare = Some(arg.clone());
+                } else {
+                    positional.push(arg.clone());
+                }
+                continue;
+            }
+            if arg == "--" {
+                only_positional = true;
+                continue;
+            }
+
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+
+            let command_options = kind.map_or(&[][..], |k| spec(k).options);
+            let option = find_option(GLOBAL_OPTIONS, name)
+                .or_else(|| find_option(command_options, name));
+            let option = match option {
+                Some(option) => option,
+                None => match kind {
+                    Some(k) => anyhow::bail!("Unknown option '{}' for {}", arg, (spec(k).handles)(config)),
+                    None => anyhow::bail!("Unknown command or option '{}'. Run 'rauri --help' for usage", arg),
+                },
+            };
+
+            let value = match (option.value, inline_value) {
+                (Some(_), Some(value)) => Some(value),
+                (Some(value_name), None) => match iter.next() {
+                    Some(value) if !value.starts_with('-') => Some(value.clone()),
+                    _ => anyhow::bail!("{} requires a value: {} {}", option.long, arg, value_name),
+                },
+                (None, Some(_)) => anyhow::bail!("{} does not take a value", option.long),
+                (None, None) => None,
+            };
+
+            match option.long {
+                "--clear" => cli.clear_aur_path = true,
+                "--path" => cli.set_path = value.map(PathBuf::from),
+                "--devel" => cli.devel = true,
+                "--dry-run" => cli.dry_run = true,
+                "--json" => cli.json = true,
+                "--help" => help = true,
+                long => options.push((long, value)),
+            }
+        }
+
+        let has = |long: &str| options.iter().any(|(l, _)| *l == long);
+        let option_value = |long: &str| options.iter()
+            .find(|(l, _)| *l == long)
+            .and_then(|(_, value)| value.clone());
+
+        cli.command = match (kind, bare) {
+            (kind, _) if help => Command::Help(kind),
+            (None, None) => Command::Help(None),
+            (None, Some(term)) => {
+                if let Some(extra) = positional.first() {
+                    anyhow::bail!("Unexpected argument '{}'", extra);
+                }
+                if Aur::is_aur_url(&term) {
+                    Command::AurUrl(term)
+                } else {
+                    Command::SearchAndInstall(term)
+                }
+            }
+            (Some(kind), _) => {
+                let spec = spec(kind);
+                if positional.len() < spec.min_args {
+                    anyhow::bail!("{} requires {}", (spec.handles)(config), spec.args);
+                }
+                if let Some(extra) = spec.max_args.and_then(|max| positional.get(max)) {
+                    anyhow::bail!("Unexpected argument '{}' for {}", extra, (spec.handles)(config));
+                }
+
+                match kind {
+                    CommandKind::Search | CommandKind::SearchAll => Command::Search {
+                        query: positional.remove(0),
+                        all: kind == CommandKind::SearchAll,
+                    },
+                    // Install without targets updates the AUR packages.
+                    CommandKind::Install if positional.is_empty() => Command::UpdateAur,
+                    CommandKind::Install => Command::Install { targets: positional, needed: has("--needed") },
+                    CommandKind::Info => Command::Info { targets: positional },
+                    CommandKind::UpdateAur => Command::UpdateAur,
+                    CommandKind::UpdateAll => Command::UpdateAll {
+                        skip_aur: has("--skip-aur"),
+                        needed: has("--needed"),
+                    },
+                    CommandKind::CheckUpdates => Command::CheckUpdates,
+                    CommandKind::Remove => Command::Remove { targets: positional },
+                    CommandKind::List => Command::List { all: false },
+                    CommandKind::ListAll => Command::List { all: true },
+                    CommandKind::UpdateMirrors => Command::UpdateMirrors,
+                    CommandKind::Adopt => Command::Adopt { targets: positional, all: has("--all") },
+                    CommandKind::Export => Command::Export {
+                        path: positional.pop().map(PathBuf::from),
+                        versions: has("--versions"),
+                    },
+                    CommandKind::Import => Command::Import {
+                        path: PathBuf::from(positional.remove(0)),
+                        diff: has("--diff"),
+                    },
+                    CommandKind::History => Command::History(Filter {
+                        package: positional.pop(),
+                        since: option_value("--since").map(|d| Ui::parse_date(&d)).transpose()?,
+                        // --until includes the whole day.
+                        until: option_value("--until").map(|d| Ui::parse_date(&d)).transpose()?.map(|t| t + 86400),
+                        ok: match option_value("--result").as_deref() {
+                            None => None,
+                            Some("ok") => Some(true),
+                            Some("failed") => Some(false),
+                            Some(other) => anyhow::bail!("--result expects ok or failed, not '{}'", other),
+                        },
+                    }),
+                    CommandKind::Rollback => Command::Rollback {
+                        version: positional.get(1).cloned(),
+                        package: positional.remove(0),
+                    },
+                    CommandKind::Completions => match option_value("--list") {
+                        Some(what) => Command::CompletionNames {
+                            what: match what.as_str() {
+                                "install" => Completion::Installable,
+                                "remove" => Completion::Installed,
+                                _ => anyhow::bail!("--list expects install or remove, not '{}'", what),
+                            },
+                            prefix: positional.pop().unwrap_or_default(),
+                        },
+                        None => Command::Completions(match positional.first().map(|s| s.as_str()) {
+                            Some("bash") => Shell::Bash,
+                            Some("zsh") => Shell::Zsh,
+                            Some("fish") => Shell::Fish,
+                            Some(other) => anyhow::bail!("Unsupported shell '{}', expected bash, zsh or fish", other),
+                            None => anyhow::bail!("completions requires a shell: bash, zsh or fish"),
+                        }),
+                    },
+                }
+            }
+        };
+
+        Ok(cli)
+    }
+}
+
+/// A command as shell completion sees it.
+pub struct CompletionEntry {
+    /// Stable identifier used inside the generated scripts, e.g. "install".
+    pub id: &'static str,
+    /// Every word that selects the command, e.g. ["-S", "--install"].
+    pub words: Vec<String>,
+    /// (long option, takes a value)
+    pub options: Vec<(&'static str, bool)>,
+    pub args: Completion,
+    pub about: String,
+}
+
+/// The commands and global options to complete, honouring the configured handles.
+/// A word that selects several commands (`-S` installs and updates) belongs to the first.
+pub fn completion_entries(config: &Config) -> (Vec<CompletionEntry>, Vec<CompletionEntry>) {
+    let mut seen: Vec<String> = Vec::new();
+
+    let commands = COMMANDS.iter().map(|spec| {
+        let words: Vec<String> = (spec.handles)(config)
+            .split(", ")
+            .map(|w| w.to_string())
+            .filter(|w| !seen.contains(w))
+            .collect();
+        seen.extend(words.iter().cloned());
+
+        CompletionEntry {
+            id: command_id(spec.kind),
+            words,
+            options: spec.options.iter().map(|o| (o.long, o.value.is_some())).collect(),
+            args: spec.completes,
+            about: spec.about.replace("{limit}", &config.search_limit.to_string()),
+        }
+    }).collect();
+
+    let globals = GLOBAL_OPTIONS.iter().map(|o| CompletionEntry {
+        id: o.long.trim_start_matches('-'),
+        words: o.short.iter().map(|s| s.to_string()).chain([o.long.to_string()]).collect(),
+        options: Vec::new(),
+        args: Completion::Nothing,
+        about: o.help.to_string(),
+    }).collect();
+
+    (commands, globals)
+}
+
+fn command_id(kind: CommandKind) -> &'static str {
+    match kind {
+        CommandKind::Search => "search",
+        CommandKind::SearchAll => "search-all",
+        CommandKind::Install => "install",
+        CommandKind::Info => "info",
+        CommandKind::UpdateAur => "update-aur",
+        CommandKind::UpdateAll => "update-all",
+        CommandKind::CheckUpdates => "check-updates",
+        CommandKind::Remove => "remove",
+        CommandKind::List => "list",
+        CommandKind::ListAll => "list-all",
+        CommandKind::UpdateMirrors => "update-mirrors",
+        CommandKind::Adopt => "adopt",
+        CommandKind::Export => "export",
+        CommandKind::Import => "import",
+        CommandKind::History => "history",
+        CommandKind::Rollback => "rollback",
+        CommandKind::Completions => "completions",
+    }
+}
+
+/// Left column of an option in help output, e.g. "-P, --path <path>".
+fn option_usage(option: &OptionSpec) -> String {
+    let names = match option.short {
+        Some(short) => format!("{}, {}", short, option.long),
+        None => format!("    {}", option.long),
+    };
+    match option.value {
+        Some(value) => format!("{} {}", names, value),
+        None => names,
+    }
+}
+
+fn command_usage(spec: &CommandSpec, config: &Config) -> String {
+    let handles = (spec.handles)(config);
+    if spec.args.is_empty() { handles } else { format!("{} {}", handles, spec.args) }
+}
+
+/// Print two aligned columns, highlighting the left one on a terminal.
+fn print_rows(rows: &[(String, String)], is_tty: bool) {
+    let width = rows.iter().map(|(left, _)| left.chars().count()).max().unwrap_or(0);
+    for (left, right) in rows {
+        let padded = format!("{:width$}", left, width = width);
+        if is_tty {
+            println!("  {}  {}", padded.yellow(), right);
+        } else {
+            println!("  {}  {}", padded, right);
+        }
+    }
+}
+
+fn print_heading(heading: &str, is_tty: bool) {
+    if is_tty {
+        println!("{}", heading.bold());
+    } else {
+        println!("{}", heading);
+    }
+}
+
+/// Print the help of one command, or the overview of all commands.
+pub fn print_help(kind: Option<CommandKind>, config: &Config) {
+    let is_tty = atty::is(Stream::Stdout);
+    println!();
+
+    if let Some(kind) = kind {
+        let spec = spec(kind);
+        print_heading("Usage:", is_tty);
+        let handle = (spec.handles)(config);
+        let handle = handle.split(", ").next().unwrap_or(&handle);
+        let options = if spec.options.is_empty() { "" } else { " [OPTIONS]" };
+        println!("  rauri {}{} {}\n", handle, options, spec.args);
+        println!("{}", spec.about.replace("{limit}", &config.search_limit.to_string()));
+
+        if !spec.options.is_empty() {
+            println!();
+            print_heading("Options:", is_tty);
+            let rows: Vec<(String, String)> = spec.options.iter()
+                .map(|o| (option_usage(o), o.help.to_string()))
+                .collect();
+            print_rows(&rows, is_tty);
+        }
+        return;
+    }
+
+    print_heading("Usage:", is_tty);
+    println!("  rauri [OPTIONS] <COMMAND> [ARGS]\n");
+
+    print_heading("Options:", is_tty);
+    let rows: Vec<(String, String)> = GLOBAL_OPTIONS.iter()
+        .map(|o| (option_usage(o), o.help.to_string()))
+        .collect();
+    print_rows(&rows, is_tty);
+
+    println!();
+    print_heading("Current AUR Path:", is_tty);
+    let current_path = config.download_dir.display().to_string();
+    if is_tty {
+        println!("  {}", current_path.green());
+    } else {
+        println!("  {}", current_path);
+    }
+
+    println!();
+    print_heading("Commands:", is_tty);
+    let mut rows: Vec<(String, String)> = COMMANDS.iter()
+        .map(|s| (command_usage(s, config),
+            s.about.replace("{limit}", &config.search_limit.to_string())))
+        .collect();
+    rows.push(("<term>".to_string(), "Search, then pick results to install (e.g. 1 3-5 ^4)".to_string()));
+    rows.push(("<AUR_URL>".to_string(), "Install from AUR git link".to_string()));
+    print_rows(&rows, is_tty);
+    println!("\nRun 'rauri <command> --help' for the options of a command.");
+
+    let config_path = Config::config_path();
+    let display_path = config_path.canonicalize().unwrap_or(config_path);
+    if is_tty {
+        println!("\n{}", format!("Configuration: {}", display_path.display()).dimmed());
+    } else {
+        println!("\nConfiguration: {}", display_path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli> {
+        let mut config = Config::default();
+        config.cmd_install = "-I".to_string();
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        Cli::parse(&args, &config)
+    }
+
+    #[test]
+    fn parses_commands_with_configured_handles() {
+        let cli = parse(&["-C", "-I", "foo", "--needed", "bar"]).unwrap();
+        assert!(cli.clear_aur_path);
+        assert_eq!(cli.command, Command::Install {
+            targets: vec!["foo".to_string(), "bar".to_string()],
+            needed: true,
+        });
+
+        assert_eq!(parse(&["--install"]).unwrap().command, Command::UpdateAur);
+        assert!(parse(&["-S", "foo"]).is_err());
+
+        let cli = parse(&["-Syu", "--skip-aur", "--devel"]).unwrap();
+        assert!(cli.devel);
+        assert_eq!(cli.command, Command::UpdateAll { skip_aur: true, needed: false });
+        assert_eq!(parse(&["-Syu", "--needed"]).unwrap().command, Command::UpdateAll { skip_aur: false, needed: true });
+        assert_eq!(parse(&["-Qu"]).unwrap().command, Command::CheckUpdates);
+        assert_eq!(parse(&["adopt", "--all"]).unwrap().command, Command::Adopt { targets: Vec::new(), all: true });
+        assert_eq!(parse(&["import", "--diff", "pkgs.toml"]).unwrap().command,
+            Command::Import { path: PathBuf::from("pkgs.toml"), diff: true });
+        assert_eq!(parse(&["export"]).unwrap().command, Command::Export { path: None, versions: false });
+        assert_eq!(parse(&["history", "foo", "--since", "2024-05-01", "--until=2024-05-01", "--result", "failed"])
+            .unwrap().command, Command::History(Filter {
+                package: Some("foo".to_string()),
+                since: Some(1714521600),
+                until: Some(1714608000),
+                ok: Some(false),
+            }));
+        assert!(parse(&["history", "--result", "maybe"]).is_err());
+        assert_eq!(parse(&["rollback", "foo", "1.0-1"]).unwrap().command,
+            Command::Rollback { package: "foo".to_string(), version: Some("1.0-1".to_string()) });
+        assert!(parse(&["rollback"]).is_err());
+
+        let cli = parse(&["-Si", "foo", "--json"]).unwrap();
+        assert!(cli.json);
+        assert_eq!(cli.command, Command::Info { targets: vec!["foo".to_string()] });
+        assert!(parse(&["--info"]).is_err());
+
+        assert_eq!(parse(&["-QA", "foo"]).unwrap().command,
+            Command::Search { query: "foo".to_string(), all: true });
+        assert_eq!(parse(&["foo"]).unwrap().command, Command::SearchAndInstall("foo".to_string()));
+        assert_eq!(parse(&[]).unwrap().command, Command::Help(None));
+        assert_eq!(parse(&["-R", "--help"]).unwrap().command, Command::Help(Some(CommandKind::Remove)));
+    }
+
+    #[test]
+    fn parses_completion_commands() {
+        assert_eq!(parse(&["completions", "zsh"]).unwrap().command, Command::Completions(Shell::Zsh));
+        assert!(parse(&["completions", "tcsh"]).is_err());
+        assert_eq!(parse(&["completions", "--list", "remove", "py"]).unwrap().command,
+            Command::CompletionNames { what: Completion::Installed, prefix: "py".to_string() });
+    }
+
+    #[test]
+    fn parses_option_values() {
+        assert_eq!(parse(&["-P", "~/aur"]).unwrap().set_path, Some(PathBuf::from("~/aur")));
+        assert_eq!(parse(&["--path=/tmp/aur"]).unwrap().set_path, Some(PathBuf::from("/tmp/aur")));
+        assert!(parse(&["-P"]).is_err());
+        assert!(parse(&["-P", "-L"]).is_err());
+    }
+
+    #[test]
+    fn rejects_misplaced_arguments() {
+        assert!(parse(&["-Q"]).is_err());
+        assert!(parse(&["-Q", "foo", "bar"]).is_err());
+        assert!(parse(&["-L", "--needed"]).is_err());
+        assert!(parse(&["-R"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
+}
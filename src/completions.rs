@@ -0,0 +1,270 @@
+//! Shell completion scripts for `rauri completions <shell>`.
+//!
+//! The scripts are generated from the CLI table with the configured command handles,
+//! so they have to be regenerated after changing `cmd_*` in the config. Package names
+//! are not baked in: the scripts call `rauri completions --list install|remove <prefix>`.
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+use crate::aur::Aur;
+use crate::cli::{self, Completion, CompletionEntry, Shell};
+use crate::config::Config;
+use crate::localdb::LocalDb;
+use crate::syncdb::SyncDb;
+use crate::ui::Ui;
+
+/// Quote `word` for a POSIX shell (and zsh).
+fn sh_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// Quote `word` for fish, which only escapes backslashes and quotes inside single quotes.
+fn fish_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+fn all_words(commands: &[CompletionEntry], globals: &[CompletionEntry]) -> Vec<String> {
+    globals.iter().chain(commands).flat_map(|e| e.words.iter().cloned()).collect()
+}
+
+fn shell_names() -> &'static str {
+    "bash zsh fish"
+}
+
+pub fn script(shell: Shell, config: &Config) -> String {
+    let (commands, globals) = cli::completion_entries(config);
+    match shell {
+        Shell::Bash => bash(&commands, &globals),
+        Shell::Zsh => zsh(&commands, &globals),
+        Shell::Fish => fish(&commands, &globals),
+    }
+}
+
+fn bash(commands: &[CompletionEntry], globals: &[CompletionEntry]) -> String {
+    let mut detect = String::new();
+    let mut complete = String::new();
+
+    for entry in commands.iter().filter(|e| !e.words.is_empty()) {
+        let pattern: Vec<String> = entry.words.iter().map(|w| sh_quote(w)).collect();
+        detect.push_str(&format!("            {}) cmd={} ;;\n", pattern.join("|"), entry.id));
+
+        let options: Vec<&str> = entry.options.iter().map(|(o, _)| *o).collect();
+        let args = match entry.args {
+            Completion::Nothing => String::new(),
+            Completion::Installable => "$(rauri completions --list install \"$cur\" 2>/dev/null)".to_string(),
+            Completion::Installed => "$(rauri completions --list remove \"$cur\" 2>/dev/null)".to_string(),
+            Completion::Files => "$(compgen -f -- \"$cur\")".to_string(),
+            Completion::Shells => shell_names().to_string(),
+        };
+        // Option names are fixed, so only the handles need quoting.
+        complete.push_str(&format!("        {}) words=\"{} {}\" ;;\n", entry.id, options.join(" "), args));
+    }
+
+    let top: Vec<String> = all_words(commands, globals);
+
+    format!(r#"# bash completion for rauri, generated by `rauri completions bash`
+_rauri() {{
+    local cur=${{COMP_WORDS[COMP_CWORD]}} prev=${{COMP_WORDS[COMP_CWORD-1]}}
+    local cmd= words i
+
+    if [[ $prev == -P || $prev == --path ]]; then
+        COMPREPLY=($(compgen -d -- "$cur"))
+        return
+    fi
+
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case ${{COMP_WORDS[i]}} in
+{detect}        esac
+        [[ -n $cmd ]] && break
+    done
+
+    case $cmd in
+{complete}        *) words={top} ;;
+    esac
+
+    COMPREPLY=($(compgen -W "$words" -- "$cur"))
+}}
+complete -F _rauri rauri
+"#, detect = detect, complete = complete, top = sh_quote(&top.join(" ")))
+}
+
+fn zsh(commands: &[CompletionEntry], globals: &[CompletionEntry]) -> String {
+    let mut detect = String::new();
+    let mut complete = String::new();
+
+    for entry in commands.iter().filter(|e| !e.words.is_empty()) {
+        let pattern: Vec<String> = entry.words.iter().map(|w| sh_quote(w)).collect();
+        detect.push_str(&format!("            {}) cmd={}; break ;;\n", pattern.join("|"), entry.id));
+
+        let options: Vec<String> = entry.options.iter().map(|(o, _)| sh_quote(o)).collect();
+        let args = match entry.args {
+            Completion::Nothing => String::new(),
+            Completion::Installable => "${(f)\"$(rauri completions --list install \"$PREFIX\" 2>/dev/null)\"}".to_string(),
+            Completion::Installed => "${(f)\"$(rauri completions --list remove \"$PREFIX\" 2>/dev/null)\"}".to_string(),
+            Completion::Files => "${PREFIX}*(N)".to_string(),
+            Completion::Shells => shell_names().to_string(),
+        };
+        complete.push_str(&format!(
+            "        {})\n            if [[ $PREFIX == -* ]]; then compadd -- {}; else compadd -- {}; fi ;;\n",
+            entry.id, options.join(" "), args,
+        ));
+    }
+
+    let mut described: Vec<String> = Vec::new();
+    for entry in globals.iter().chain(commands) {
+        for word in &entry.words {
+            described.push(sh_quote(&format!("{}:{}", word.replace(':', r"\:"), entry.about)));
+        }
+    }
+
+    format!(r#"#compdef rauri
+# zsh completion for rauri, generated by `rauri completions zsh`
+
+_rauri() {{
+    local cmd= word
+    local -a commands
+    commands=(
+        {described}
+    )
+
+    if [[ ${{words[CURRENT-1]}} == (-P|--path) ]]; then
+        _files -/
+        return
+    fi
+
+    for word in ${{words[2,CURRENT-1]}}; do
+        case $word in
+{detect}        esac
+    done
+
+    case $cmd in
+{complete}        *) _describe -t commands 'rauri command' commands ;;
+    esac
+}}
+
+if [[ $funcstack[1] == _rauri ]]; then
+    _rauri "$@"
+else
+    compdef _rauri rauri
+fi
+"#, described = described.join("\n        "), detect = detect, complete = complete)
+}
+
+fn fish(commands: &[CompletionEntry], globals: &[CompletionEntry]) -> String {
+    let mut detect = String::new();
+    let mut lines = String::new();
+
+    for entry in commands.iter().filter(|e| !e.words.is_empty()) {
+        let words: Vec<String> = entry.words.iter().map(|w| fish_quote(w)).collect();
+        detect.push_str(&format!(
+            "        if contains -- $word {}\n            echo {}\n            return 0\n        end\n",
+            words.join(" "), entry.id,
+        ));
+
+        for word in &entry.words {
+            lines.push_str(&format!("complete -c rauri -n 'not __rauri_command >/dev/null' -a {} -d {}\n",
+                fish_quote(word), fish_quote(&entry.about)));
+        }
+
+        let condition = fish_quote(&format!("__rauri_is {}", entry.id));
+        for (option, takes_value) in &entry.options {
+            let flag = if *takes_value { " -r" } else { "" };
+            lines.push_str(&format!("complete -c rauri -n {} -l {}{}\n",
+                condition, option.trim_start_matches("--"), flag));
+        }
+        let args = match entry.args {
+            Completion::Nothing => continue,
+            Completion::Installable => "(rauri completions --list install (commandline -ct) 2>/dev/null)".to_string(),
+            Completion::Installed => "(rauri completions --list remove (commandline -ct) 2>/dev/null)".to_string(),
+            Completion::Files => "(__fish_complete_path (commandline -ct))".to_string(),
+            Completion::Shells => shell_names().to_string(),
+        };
+        lines.push_str(&format!("complete -c rauri -n {} -a {}\n", condition, fish_quote(&args)));
+    }
+
+    for entry in globals {
+        let mut line = String::from("complete -c rauri");
+        for word in &entry.words {
+            match word.strip_prefix("--") {
+                Some(long) => line.push_str(&format!(" -l {}", long)),
+                None => line.push_str(&format!(" -o {}", word.trim_start_matches('-'))),
+            }
+        }
+        if entry.id == "path" {
+            line.push_str(" -r -a '(__fish_complete_directories)'");
+        }
+        line.push_str(&format!(" -d {}\n", fish_quote(&entry.about)));
+        lines.push_str(&line);
+    }
+
+    format!(r#"# fish completion for rauri, generated by `rauri completions fish`
+
+function __rauri_command
+    for word in (commandline -opc)[2..-1]
+{detect}    end
+    return 1
+end
+
+function __rauri_is
+    string match -q -- $argv[1] (__rauri_command)
+end
+
+complete -c rauri -f
+{lines}"#, detect = detect, lines = lines)
+}
+
+/// Print the package names to complete for `what` that start with `prefix`.
+pub fn print_names(what: Completion, prefix: &str) -> Result<()> {
+    // Anything else on stdout would be offered as a name.
+    Ui::set_quiet(true);
+    let mut names: BTreeSet<String> = BTreeSet::new();
+
+    match what {
+        Completion::Installable => {
+            if let Ok(sync_db) = SyncDb::load() {
+                names.extend(sync_db.packages().map(|p| p.name.clone()));
+            }
+            // Completion must never fail loudly or wait for the network; without a cached
+            // name list only repo packages are offered.
+            if let Ok(aur_names) = Aur::cached_package_names() {
+                names.extend(aur_names);
+            }
+        }
+        Completion::Installed => {
+            let local_db = LocalDb::load()?;
+            let sync_names = SyncDb::load()
+                .map(|db| db.packages().map(|p| p.name.clone()).collect())
+                .unwrap_or_default();
+            names.extend(local_db.foreign(&sync_names).map(|p| p.name.clone()));
+        }
+        Completion::Nothing | Completion::Files | Completion::Shells => {
+            Ui::warning("Nothing to list");
+        }
+    }
+
+    for name in names.iter().filter(|n| n.starts_with(prefix)) {
+        println!("{}", name);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_use_configured_handles() {
+        let mut config = Config::default();
+        config.cmd_install = "-I".to_string();
+
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(shell, &config);
+            assert!(script.contains("'-I'"), "{:?} script misses the configured handle", shell);
+            assert!(script.contains("--list install"));
+            assert!(script.contains("--list remove"));
+            assert!(!script.contains("'-S'"));
+        }
+    }
+}
@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::fs;
 
+use crate::paths;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub download_dir: PathBuf,
@@ -15,6 +17,21 @@ pub struct Config {
     /// Run reflector to update mirrorlist before system/AUR updates.
     #[serde(default = "default_update_mirrors")]
     pub update_mirrors: bool,
+    /// Also rebuild VCS packages (-git, -svn, ...) when their upstream has new commits.
+    /// Can be enabled for a single run with --devel.
+    #[serde(default)]
+    pub devel: bool,
+    /// Set by --dry-run for a single run: print what would be done instead of doing it.
+    #[serde(skip)]
+    pub dry_run: bool,
+    /// Show PKGBUILD and .install files (or the diff since the last review) and ask for
+    /// confirmation before building AUR packages. Uses `editor` if set, else $PAGER.
+    #[serde(default = "default_review")]
+    pub review: bool,
+    /// Built archives kept per AUR package for `rauri rollback`, in ~/.cache/rauri/builds.
+    /// 0 keeps none.
+    #[serde(default = "default_keep_builds")]
+    pub keep_builds: usize,
     /// Short flag for search (default: -Q). Long form --search always works.
     #[serde(default = "default_cmd_search")]
     pub cmd_search: String,
@@ -33,39 +50,53 @@ pub struct Config {
     /// Short flag for updating mirrors with reflector (default: -M). Long form --update-mirrors always works.
     #[serde(default = "default_cmd_update_mirrors")]
     pub cmd_update_mirrors: String,
+    /// Short flag for package info (default: -Si). Long form --info always works.
+    #[serde(default = "default_cmd_info")]
+    pub cmd_info: String,
+    /// Short flag for listing pending updates (default: -Qu). Long form --check-updates always works.
+    #[serde(default = "default_cmd_check_updates")]
+    pub cmd_check_updates: String,
 }
 
 fn default_use_color() -> bool { true }
 fn default_search_limit() -> usize { 15 }
 fn default_update_mirrors() -> bool { true }
+fn default_review() -> bool { true }
+fn default_keep_builds() -> usize { 3 }
 fn default_cmd_search() -> String { "-Q".to_string() }
 fn default_cmd_install() -> String { "-S".to_string() }
 fn default_cmd_update_all() -> String { "-Syu".to_string() }
 fn default_cmd_remove() -> String { "-R".to_string() }
 fn default_cmd_list() -> String { "-L".to_string() }
 fn default_cmd_update_mirrors() -> String { "-M".to_string() }
+fn default_cmd_info() -> String { "-Si".to_string() }
+fn default_cmd_check_updates() -> String { "-Qu".to_string() }
 
 impl Config {
     pub fn default() -> Self {
-        let home = dirs::home_dir().expect("Failed to get home directory");
         Config {
-            download_dir: home.join("Downloads").join("aur"),
+            download_dir: paths::home_dir().join("Downloads").join("aur"),
             use_color: true,
             editor: None,
             search_limit: default_search_limit(),
             update_mirrors: default_update_mirrors(),
+            devel: false,
+            dry_run: false,
+            review: default_review(),
+            keep_builds: default_keep_builds(),
             cmd_search: default_cmd_search(),
             cmd_install: default_cmd_install(),
             cmd_update_all: default_cmd_update_all(),
             cmd_remove: default_cmd_remove(),
             cmd_list: default_cmd_list(),
             cmd_update_mirrors: default_cmd_update_mirrors(),
+            cmd_info: default_cmd_info(),
+            cmd_check_updates: default_cmd_check_updates(),
         }
     }
 
     pub fn config_path() -> PathBuf {
-        let home = dirs::home_dir().expect("Failed to get home directory");
-        home.join(".config").join("rauri").join("config.toml")
+        paths::config_dir().join("config.toml")
     }
 
     pub fn load() -> Result<Self> {
@@ -77,12 +108,7 @@ impl Config {
             let mut config: Config = toml::from_str(&content)
                 .with_context(|| "Failed to parse config file")?;
 
-            if let Some(path_str) = config.download_dir.to_str() {
-                if path_str.starts_with('~') {
-                    let home = dirs::home_dir().expect("Failed to get home directory");
-                    config.download_dir = home.join(path_str[1..].trim_start_matches('/'));
-                }
-            }
+            config.download_dir = paths::expand_home(config.download_dir);
 
             Ok(config)
         } else {
@@ -124,14 +150,7 @@ impl Config {
             return Ok(default);
         }
 
-        let mut path = PathBuf::from(response);
-
-        if response.starts_with('~') {
-            let home = dirs::home_dir().expect("Failed to get home directory");
-            path = home.join(response[1..].trim_start_matches('/'));
-        }
-
-        Ok(path)
+        Ok(paths::expand_home(PathBuf::from(response)))
     }
 
     // --- Command matching helpers ---
@@ -172,4 +191,12 @@ impl Config {
     pub fn is_update_mirrors_cmd(&self, cmd: &str) -> bool {
         cmd == self.cmd_update_mirrors || cmd == "--update-mirrors"
     }
+
+    pub fn is_info_cmd(&self, cmd: &str) -> bool {
+        cmd == self.cmd_info || cmd == "--info"
+    }
+
+    pub fn is_check_updates_cmd(&self, cmd: &str) -> bool {
+        cmd == self.cmd_check_updates || cmd == "--check-updates"
+    }
 }
@@ -0,0 +1,411 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+use crate::aur::{Aur, AurPackage};
+use crate::runner::Cmd;
+use crate::syncdb::SyncDb;
+use crate::ui::Ui;
+
+/// Strip a version constraint from a dependency string, e.g. "python>=3.11" -> "python".
+pub fn dep_name(dep: &str) -> &str {
+    dep.split(['<', '>', '='])
+        .next()
+        .unwrap_or(dep)
+        .trim()
+}
+
+/// Everything needed to install a set of AUR packages, worked out before anything is built.
+#[derive(Debug, Default)]
+pub struct InstallPlan {
+    /// Official repository packages each of the AUR packages below needs, by its pkgname.
+    pub repo_deps: HashMap<String, Vec<String>>,
+    /// AUR packages in build order: every package comes after the AUR packages it depends on.
+    pub aur_order: Vec<AurPackage>,
+}
+
+/// Where the resolver looks up AUR packages: the RPC, or canned packages in tests.
+trait AurSource {
+    /// The packages of `names` the AUR has, by name.
+    fn packages_info(&self, names: &[&str]) -> Result<HashMap<String, AurPackage>>;
+    /// Names of the AUR packages that provide `name`.
+    fn find_providers(&self, name: &str) -> Result<Vec<String>>;
+}
+
+impl AurSource for Aur {
+    fn packages_info(&self, names: &[&str]) -> Result<HashMap<String, AurPackage>> {
+        Aur::get_packages_info(names)
+    }
+
+    fn find_providers(&self, name: &str) -> Result<Vec<String>> {
+        Aur::providers(name)
+    }
+}
+
+pub struct DependencyResolver {
+    /// Resolved AUR packages by pkgname.
+    aur: HashMap<String, AurPackage>,
+    /// Dependencies satisfied by the provides of a resolved AUR package → its pkgname.
+    provided: HashMap<String, String>,
+    /// Dependencies satisfied by an official package → its name.
+    repo: HashMap<String, String>,
+    /// (dependency, required by)
+    missing: Vec<(String, String)>,
+}
+
+/// Ask pacman which of `deps` are not satisfied by the installed packages.
+/// `pacman -T` understands version constraints and provides, and prints only the
+/// unsatisfied entries.
+fn unsatisfied(deps: &[String]) -> Vec<String> {
+    if deps.is_empty() {
+        return Vec::new();
+    }
+
+    match Cmd::new("pacman").arg("-T").args(deps).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect(),
+        // Without pacman we cannot tell, so treat everything as missing.
+        Err(_) => deps.to_vec(),
+    }
+}
+
+impl DependencyResolver {
+    /// Resolve the full dependency tree of the given AUR packages.
+    ///
+    /// Fails before anything is built if neither the official repositories nor the AUR
+    /// have a package of that name or one that provides it, or if the AUR packages
+    /// depend on each other in a cycle.
+    pub fn resolve(targets: &[&str]) -> Result<InstallPlan> {
+        let sync_db = SyncDb::load_or_warn();
+        Self::resolve_with(targets, &sync_db, &Aur)
+    }
+
+    fn resolve_with(targets: &[&str], sync_db: &SyncDb, source: &dyn AurSource) -> Result<InstallPlan> {
+        let mut resolver = DependencyResolver {
+            aur: HashMap::new(),
+            provided: HashMap::new(),
+            repo: HashMap::new(),
+            missing: Vec::new(),
+        };
+
+        // `rauri -S foo foo` asks for foo once.
+        let mut unique: Vec<&str> = Vec::new();
+        for target in targets {
+            if !unique.contains(target) {
+                unique.push(target);
+            }
+        }
+        let targets = &unique[..];
+
+        let mut found = source.packages_info(targets)?;
+        for target in targets {
+            match found.remove(*target) {
+                Some(pkg) => {
+                    resolver.aur.insert(pkg.name.clone(), pkg);
+                }
+                None => anyhow::bail!("Package not found in official repositories or AUR: {}", target),
+            }
+        }
+
+        // Walk the tree one level at a time so each level costs a single AUR request.
+        let mut level: Vec<String> = targets.iter().map(|t| t.to_string()).collect();
+        let mut seen: HashSet<String> = HashSet::new();
+
+        while !level.is_empty() {
+            // (dependency, required by)
+            let mut pending: Vec<(String, String)> = Vec::new();
+            for name in level.drain(..) {
+                for dep in Self::build_deps(&resolver.aur[&name]) {
+                    if seen.insert(dep.to_string()) {
+                        pending.push((dep.to_string(), name.clone()));
+                    }
+                }
+            }
+
+            let deps: Vec<String> = pending.iter().map(|(d, _)| d.clone()).collect();
+            let unsatisfied: HashSet<String> = unsatisfied(&deps).into_iter().collect();
+
+            let mut aur_candidates: Vec<(String, String)> = Vec::new();
+            for (dep, required_by) in pending {
+                if !unsatisfied.contains(&dep) {
+                    continue;
+                }
+
+                let dep_pkg = dep_name(&dep).to_string();
+                if resolver.resolved(&dep_pkg).is_some() {
+                    continue;
+                }
+                if resolver.repo.values().any(|official| *official == dep_pkg) {
+                    resolver.repo.insert(dep, dep_pkg);
+                    continue;
+                }
+
+                // Official packages may satisfy the dependency through their provides.
+                let official = sync_db.satisfier(&dep).map(|p| p.name.clone())
+                    .or_else(|| sync_db.unread_satisfier(&dep));
+                match official {
+                    Some(official) => {
+                        resolver.repo.insert(dep, official);
+                    }
+                    None => aur_candidates.push((dep, required_by)),
+                }
+            }
+
+            let names: Vec<&str> = aur_candidates.iter().map(|(d, _)| dep_name(d)).collect();
+            let found = source.packages_info(&names)?;
+
+            for (dep, required_by) in &aur_candidates {
+                let dep_pkg = dep_name(dep);
+                if resolver.resolved(dep_pkg).is_some() {
+                    continue;
+                }
+                let aur_pkg = match found.get(dep_pkg) {
+                    Some(aur_pkg) => Some(aur_pkg.clone()),
+                    None => Self::aur_provider(dep_pkg, source)?,
+                };
+                match aur_pkg {
+                    Some(aur_pkg) => {
+                        if aur_pkg.name != dep_pkg {
+                            resolver.provided.insert(dep_pkg.to_string(), aur_pkg.name.clone());
+                        }
+                        if !resolver.aur.contains_key(&aur_pkg.name) {
+                            level.push(aur_pkg.name.clone());
+                            resolver.aur.insert(aur_pkg.name.clone(), aur_pkg);
+                        }
+                    }
+                    None => resolver.missing.push((dep.clone(), required_by.clone())),
+                }
+            }
+        }
+
+        if !resolver.missing.is_empty() {
+            let list: Vec<String> = resolver.missing.iter()
+                .map(|(dep, by)| format!("{} (required by {})", dep, by))
+                .collect();
+            anyhow::bail!("Unresolvable dependencies: {}", list.join(", "));
+        }
+
+        let aur_order = resolver.build_order()?;
+        let repo_deps = aur_order.iter()
+            .map(|pkg| {
+                let mut official: Vec<String> = Self::build_deps(pkg)
+                    .filter_map(|dep| resolver.repo.get(dep).cloned())
+                    .collect();
+                official.sort();
+                official.dedup();
+                (pkg.name.clone(), official)
+            })
+            .filter(|(_, official)| !official.is_empty())
+            .collect();
+
+        Ok(InstallPlan {
+            repo_deps,
+            aur_order,
+        })
+    }
+
+    /// The resolved AUR package that satisfies `name`, by its name or its provides.
+    /// Among several providers the first by name is picked, so plans are repeatable.
+    fn resolved(&mut self, name: &str) -> Option<&str> {
+        if !self.aur.contains_key(name) && !self.provided.contains_key(name) {
+            let provider = self.aur.values()
+                .filter(|p| p.provides.iter().any(|provide| dep_name(provide) == name))
+                .min_by(|a, b| a.name.cmp(&b.name))?;
+            self.provided.insert(name.to_string(), provider.name.clone());
+        }
+        self.resolved_name(name)
+    }
+
+    fn resolved_name(&self, name: &str) -> Option<&str> {
+        match self.aur.get_key_value(name) {
+            Some((key, _)) => Some(key),
+            None => self.provided.get(name).map(|p| p.as_str()),
+        }
+    }
+
+    /// The AUR package to install for `name` that no package is called, among those that
+    /// provide it. With several, the most voted for is picked.
+    fn aur_provider(name: &str, source: &dyn AurSource) -> Result<Option<AurPackage>> {
+        let names = source.find_providers(name)?;
+        if names.is_empty() {
+            return Ok(None);
+        }
+        let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+        let mut providers: Vec<AurPackage> = source.packages_info(&names)?
+            .into_values()
+            .filter(|p| p.provides.iter().any(|provide| dep_name(provide) == name))
+            .collect();
+        providers.sort_by(|a, b| b.votes.cmp(&a.votes).then_with(|| a.name.cmp(&b.name)));
+
+        if providers.len() > 1 {
+            let others: Vec<&str> = providers[1..].iter().map(|p| p.name.as_str()).collect();
+            Ui::info(&format!("Using {} for {} (also provided by {})", providers[0].name, name, others.join(", ")));
+        }
+        Ok(providers.into_iter().next())
+    }
+
+    /// All dependencies makepkg needs to build and install `pkg`.
+    fn build_deps(pkg: &AurPackage) -> impl Iterator<Item = &str> {
+        pkg.depends.iter()
+            .chain(&pkg.make_depends)
+            .chain(&pkg.check_depends)
+            .map(|d| d.as_str())
+    }
+
+    /// Topologically sort the resolved AUR packages, failing on dependency cycles.
+    fn build_order(&self) -> Result<Vec<AurPackage>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark { Visiting, Done }
+
+        fn visit(
+            name: &str,
+            resolver: &DependencyResolver,
+            marks: &mut HashMap<String, Mark>,
+            stack: &mut Vec<String>,
+            order: &mut Vec<AurPackage>,
+        ) -> Result<()> {
+            match marks.get(name) {
+                Some(Mark::Done) => return Ok(()),
+                Some(Mark::Visiting) => {
+                    let start = stack.iter().position(|n| n == name).unwrap_or(0);
+                    let mut cycle = stack[start..].to_vec();
+                    cycle.push(name.to_string());
+                    anyhow::bail!("Dependency cycle detected: {}", cycle.join(" -> "));
+                }
+                None => {}
+            }
+
+            marks.insert(name.to_string(), Mark::Visiting);
+            stack.push(name.to_string());
+
+            let pkg = &resolver.aur[name];
+            let mut aur_deps: Vec<&str> = DependencyResolver::build_deps(pkg)
+                .filter_map(|d| resolver.resolved_name(dep_name(d)))
+                .collect();
+            aur_deps.sort();
+            aur_deps.dedup();
+
+            for dep in aur_deps {
+                visit(dep, resolver, marks, stack, order)?;
+            }
+
+            stack.pop();
+            marks.insert(name.to_string(), Mark::Done);
+            order.push(pkg.clone());
+            Ok(())
+        }
+
+        let mut names: Vec<&String> = self.aur.keys().collect();
+        names.sort();
+
+        let mut marks = HashMap::new();
+        let mut stack = Vec::new();
+        let mut order = Vec::new();
+        for name in names {
+            visit(name, self, &mut marks, &mut stack, &mut order)?;
+        }
+
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, FakeRunner};
+
+    fn package(name: &str, depends: &[&str], provides: &[&str]) -> AurPackage {
+        AurPackage {
+            name: name.to_string(),
+            package_base: name.to_string(),
+            version: "1.0-1".to_string(),
+            depends: depends.iter().map(|d| d.to_string()).collect(),
+            provides: provides.iter().map(|p| p.to_string()).collect(),
+            ..AurPackage::default()
+        }
+    }
+
+    /// An AUR with just these packages.
+    struct Canned(Vec<AurPackage>);
+
+    impl AurSource for Canned {
+        fn packages_info(&self, names: &[&str]) -> Result<HashMap<String, AurPackage>> {
+            Ok(self.0.iter()
+                .filter(|p| names.contains(&p.name.as_str()))
+                .map(|p| (p.name.clone(), p.clone()))
+                .collect())
+        }
+
+        fn find_providers(&self, name: &str) -> Result<Vec<String>> {
+            Ok(self.0.iter()
+                .filter(|p| p.provides.iter().any(|provide| dep_name(provide) == name))
+                .map(|p| p.name.clone())
+                .collect())
+        }
+    }
+
+    fn resolver(packages: &[AurPackage]) -> DependencyResolver {
+        DependencyResolver {
+            aur: packages.iter().map(|p| (p.name.clone(), p.clone())).collect(),
+            provided: HashMap::new(),
+            repo: HashMap::new(),
+            missing: Vec::new(),
+        }
+    }
+
+    fn names(packages: &[AurPackage]) -> Vec<&str> {
+        packages.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn orders_builds_and_detects_cycles() {
+        let order = resolver(&[
+            package("app", &["libb", "libc>=2", "glibc"], &[]),
+            package("libb", &["libc"], &[]),
+            package("libc", &[], &[]),
+        ]).build_order().unwrap();
+        assert_eq!(names(&order), vec!["libc", "libb", "app"]);
+
+        // Several resolved packages provide libfoo; the choice doesn't depend on hashing.
+        let mut providers = resolver(&[
+            package("libfoo-git", &[], &["libfoo"]),
+            package("libfoo-bin", &[], &["libfoo=1.2"]),
+            package("libfoo-nightly", &[], &["libfoo"]),
+        ]);
+        assert_eq!(providers.resolved("libfoo"), Some("libfoo-bin"));
+
+        let error = resolver(&[
+            package("app", &["liba"], &[]),
+            package("liba", &["libb"], &[]),
+            package("libb", &["liba"], &[]),
+        ]).build_order().unwrap_err();
+        assert_eq!(error.to_string(), "Dependency cycle detected: liba -> libb -> liba");
+    }
+
+    #[test]
+    fn resolves_providers_and_reports_missing_dependencies() {
+        let fake = FakeRunner::new();
+        // glibc is installed, the rest is not.
+        fake.on("pacman -T", 127, "libfoo>=1\nnothere\n");
+        let _runner = runner::install(fake.clone());
+        let sync_db = SyncDb::default();
+
+        let aur = Canned(vec![
+            package("app", &["libfoo>=1", "glibc"], &[]),
+            package("broken", &["libfoo>=1", "nothere", "glibc"], &[]),
+            package("libfoo-bin", &[], &["libfoo=1.2"]),
+        ]);
+
+        let plan = DependencyResolver::resolve_with(&["app"], &sync_db, &aur).unwrap();
+        assert_eq!(names(&plan.aur_order), vec!["libfoo-bin", "app"]);
+        assert!(plan.repo_deps.is_empty());
+
+        let plan = DependencyResolver::resolve_with(&["app", "app"], &sync_db, &aur).unwrap();
+        assert_eq!(names(&plan.aur_order), vec!["libfoo-bin", "app"]);
+
+        let error = DependencyResolver::resolve_with(&["broken"], &sync_db, &aur).unwrap_err();
+        assert_eq!(error.to_string(), "Unresolvable dependencies: nothere (required by broken)");
+    }
+}
@@ -0,0 +1,245 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::paths;
+use crate::runner::Cmd;
+use crate::srcinfo::{self, Srcinfo};
+
+/// Suffixes of VCS packages whose pkgver only changes when they are rebuilt.
+const DEVEL_SUFFIXES: &[&str] = &["-git", "-svn", "-hg", "-bzr", "-darcs", "-cvs"];
+
+pub fn is_devel(package_name: &str) -> bool {
+    DEVEL_SUFFIXES.iter().any(|s| package_name.ends_with(s))
+}
+
+/// A git source from a .SRCINFO that follows a moving ref.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VcsSource {
+    pub url: String,
+    /// Branch name, or "HEAD" when the source doesn't pin one.
+    pub git_ref: String,
+}
+
+impl VcsSource {
+    /// Parse a makepkg source entry such as `name::git+https://host/repo.git#branch=main`.
+    /// Returns None for non-git sources and for sources pinned to a tag or commit,
+    /// which never change upstream.
+    pub fn parse(source: &str) -> Option<Self> {
+        let source = source.split_once("::").map_or(source, |(_, s)| s);
+
+        let url = if let Some(rest) = source.strip_prefix("git+") {
+            rest
+        } else if source.starts_with("git://") {
+            source
+        } else {
+            return None;
+        };
+
+        let (url, fragment) = match url.split_once('#') {
+            Some((u, f)) => (u, Some(f)),
+            None => (url, None),
+        };
+        // Query strings such as ?signed are makepkg options, not part of the URL.
+        let url = url.split('?').next().unwrap_or(url);
+
+        let git_ref = match fragment.and_then(|f| f.split_once('=')) {
+            Some(("branch", branch)) => branch.to_string(),
+            Some(_) => return None,
+            None => "HEAD".to_string(),
+        };
+
+        Some(VcsSource { url: url.to_string(), git_ref })
+    }
+
+    fn key(&self) -> String {
+        format!("{}#{}", self.url, self.git_ref)
+    }
+}
+
+/// Collect the moving git sources of a package for the current architecture.
+pub fn vcs_sources(srcinfo: &Srcinfo) -> Vec<VcsSource> {
+    let mut sources: Vec<VcsSource> = srcinfo::for_arch(&srcinfo.base.source, std::env::consts::ARCH)
+        .filter_map(VcsSource::parse)
+        .collect();
+    sources.dedup();
+    sources
+}
+
+/// Ask the remote which commit `git_ref` currently points at.
+pub fn ls_remote(source: &VcsSource) -> Result<String> {
+    let output = Cmd::new("git")
+        .arg("ls-remote")
+        .arg(&source.url)
+        .arg(&source.git_ref)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .context("Failed to execute git ls-remote")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git ls-remote {} failed: {}", source.url, stderr.trim());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.split_whitespace()
+        .next()
+        .map(|s| s.to_string())
+        .with_context(|| format!("{} has no ref {}", source.url, source.git_ref))
+}
+
+/// Upstream commits each devel package was last built from, keyed by package name and
+/// then by source URL and ref.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DevelStore {
+    #[serde(default)]
+    packages: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl DevelStore {
+    pub fn store_path() -> PathBuf {
+        paths::config_dir().join("devel.toml")
+    }
+
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::store_path())
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read devel file: {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| "Failed to parse devel file")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::store_path())
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create config directory: {}", parent.display()))?;
+        }
+
+        let content = toml::to_string_pretty(self)
+            .context("Failed to serialize devel data")?;
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write devel file: {}", path.display()))?;
+
+        Ok(())
+    }
+
+    pub fn is_recorded(&self, package_name: &str) -> bool {
+        self.packages.contains_key(package_name)
+    }
+
+    /// Remember the commits the given sources point at right now, i.e. what was just built.
+    pub fn record(&mut self, package_name: &str, sources: &[VcsSource]) -> Result<()> {
+        let mut commits = BTreeMap::new();
+        for source in sources {
+            commits.insert(source.key(), ls_remote(source)?);
+        }
+        self.packages.insert(package_name.to_string(), commits);
+        Ok(())
+    }
+
+    pub fn forget(&mut self, package_name: &str) {
+        self.packages.remove(package_name);
+    }
+
+    /// True if any source has moved upstream since the package was recorded, or if a
+    /// source was added that we have no commit for.
+    pub fn has_upstream_changes(&self, package_name: &str, sources: &[VcsSource]) -> Result<bool> {
+        let recorded = match self.packages.get(package_name) {
+            Some(r) => r,
+            None => return Ok(false),
+        };
+
+        for source in sources {
+            match recorded.get(&source.key()) {
+                Some(commit) if *commit == ls_remote(source)? => {}
+                _ => return Ok(true),
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=rauri", "-c", "user.email=rauri@localhost"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .expect("failed to run git");
+        assert!(status.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&status.stderr));
+    }
+
+    #[test]
+    fn parses_vcs_sources() {
+        // Sources for this machine's architecture count, other architectures' don't.
+        let other_arch = if std::env::consts::ARCH == "aarch64" { "x86_64" } else { "aarch64" };
+        let srcinfo = Srcinfo::parse(&format!("pkgbase = foo-git\n\
+            \tpkgver = r1.abc\n\
+            \tpkgrel = 1\n\
+            \tsource = foo::git+https://example.com/foo.git#branch=dev\n\
+            \tsource = https://example.com/patch.diff\n\
+            \tsource = git+https://example.com/pinned.git#tag=v1.0\n\
+            \tsource = git://example.com/bar.git\n\
+            \tsource_{} = git+https://example.com/native.git#branch=main\n\
+            \tsource_{} = git+https://example.com/foreign.git\n\
+            pkgname = foo-git\n", std::env::consts::ARCH, other_arch)).unwrap();
+
+        assert_eq!(vcs_sources(&srcinfo), vec![
+            VcsSource { url: "https://example.com/foo.git".into(), git_ref: "dev".into() },
+            VcsSource { url: "git://example.com/bar.git".into(), git_ref: "HEAD".into() },
+            VcsSource { url: "https://example.com/native.git".into(), git_ref: "main".into() },
+        ]);
+    }
+
+    #[test]
+    fn detects_new_upstream_commits() {
+        let tmp = tempfile::tempdir().unwrap();
+        let upstream = tmp.path().join("upstream.git");
+        let work = tmp.path().join("work");
+        fs::create_dir_all(&upstream).unwrap();
+        fs::create_dir_all(&work).unwrap();
+
+        git(&upstream, &["init", "--bare", "-b", "main"]);
+        git(&work, &["init", "-b", "main"]);
+        git(&work, &["commit", "--allow-empty", "-m", "first"]);
+        git(&work, &["push", upstream.to_str().unwrap(), "main"]);
+
+        let sources = vec![VcsSource {
+            url: upstream.to_str().unwrap().to_string(),
+            git_ref: "main".to_string(),
+        }];
+
+        let mut store = DevelStore::default();
+        assert!(!store.has_upstream_changes("foo-git", &sources).unwrap());
+
+        store.record("foo-git", &sources).unwrap();
+        assert!(!store.has_upstream_changes("foo-git", &sources).unwrap());
+
+        git(&work, &["commit", "--allow-empty", "-m", "second"]);
+        git(&work, &["push", upstream.to_str().unwrap(), "main"]);
+        assert!(store.has_upstream_changes("foo-git", &sources).unwrap());
+
+        let path = tmp.path().join("devel.toml");
+        store.save_to(&path).unwrap();
+        assert!(DevelStore::load_from(&path).unwrap().is_recorded("foo-git"));
+    }
+}
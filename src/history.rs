@@ -0,0 +1,204 @@
+//! Record of what rauri did, in `~/.config/rauri/history.jsonl`.
+//!
+//! Every install, update, removal and cleared checkout appends one JSON object per
+//! package to the file, which is never rewritten. `rauri history` filters it by
+//! package, date range and outcome.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::paths;
+use crate::render::Renderer;
+use crate::ui::Ui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Install,
+    Update,
+    Remove,
+    /// A checkout deleted by `-C`.
+    Clear,
+    /// An earlier build reinstalled by `rauri rollback`.
+    Rollback,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    /// Unix timestamp of when the action finished.
+    pub time: i64,
+    pub action: Action,
+    /// The package, or the pkgbase of a cleared checkout.
+    pub package: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    /// "aur" or "official".
+    pub source: String,
+    pub ok: bool,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl Event {
+    pub fn new(action: Action, package: &str, source: &str) -> Self {
+        Event {
+            time: now(),
+            action,
+            package: package.to_string(),
+            old_version: None,
+            new_version: None,
+            source: source.to_string(),
+            ok: true,
+            error: None,
+            duration_ms: 0,
+        }
+    }
+
+    pub fn versions(mut self, old: Option<&str>, new: Option<&str>) -> Self {
+        self.old_version = old.map(|v| v.to_string());
+        self.new_version = new.map(|v| v.to_string());
+        self
+    }
+
+    pub fn took(mut self, duration: Duration) -> Self {
+        self.duration_ms = duration.as_millis() as u64;
+        self
+    }
+
+    pub fn result<T>(mut self, result: &Result<T>) -> Self {
+        if let Err(e) = result {
+            self.ok = false;
+            self.error = Some(format!("{:#}", e));
+            self.new_version = None;
+        }
+        self
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Which events `rauri history` shows.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    pub package: Option<String>,
+    /// Unix timestamps; `until` is exclusive.
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    /// Only successful (true) or failed (false) events.
+    pub ok: Option<bool>,
+}
+
+impl Filter {
+    fn matches(&self, event: &Event) -> bool {
+        self.package.as_ref().is_none_or(|p| *p == event.package)
+            && self.since.is_none_or(|since| event.time >= since)
+            && self.until.is_none_or(|until| event.time < until)
+            && self.ok.is_none_or(|ok| event.ok == ok)
+    }
+}
+
+pub struct History;
+
+impl History {
+    pub fn history_path() -> PathBuf {
+        paths::config_dir().join("history.jsonl")
+    }
+
+    /// Append `events` to the history. A failure to write is only a warning; the
+    /// actions themselves already happened.
+    pub fn record(events: &[Event]) {
+        if let Err(e) = Self::append(&Self::history_path(), events) {
+            Ui::warning(&format!("Failed to write history: {}", e));
+        }
+    }
+
+    fn append(path: &Path, events: &[Event]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create config directory: {}", parent.display()))?;
+        }
+
+        let mut lines = String::new();
+        for event in events {
+            lines.push_str(&serde_json::to_string(event)?);
+            lines.push('\n');
+        }
+        // One write per call, so concurrent runs don't interleave within a line.
+        OpenOptions::new().create(true).append(true).open(path)
+            .and_then(|mut file| file.write_all(lines.as_bytes()))
+            .with_context(|| format!("Failed to append to {}", path.display()))
+    }
+
+    /// Recorded events matching `filter`, oldest first.
+    pub fn load(filter: &Filter) -> Result<Vec<Event>> {
+        Self::read(&Self::history_path(), filter)
+    }
+
+    /// Events matching `filter`, oldest first. Lines that can't be parsed are skipped.
+    fn read(path: &Path, filter: &Filter) -> Result<Vec<Event>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+
+        Ok(content.lines()
+            .filter_map(|line| serde_json::from_str::<Event>(line).ok())
+            .filter(|event| filter.matches(event))
+            .collect())
+    }
+
+    pub fn show(filter: &Filter, renderer: &dyn Renderer) -> Result<()> {
+        renderer.history(&Self::load(filter)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_and_filters_events() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("rauri").join("history.jsonl");
+
+        let mut install = Event::new(Action::Install, "foo", "aur")
+            .versions(None, Some("1.0-1"))
+            .took(Duration::from_millis(1500));
+        install.time = 1714521600;
+        let mut update = Event::new(Action::Update, "foo", "aur")
+            .versions(Some("1.0-1"), Some("1.1-1"))
+            .result(&Err::<(), _>(anyhow::anyhow!("makepkg failed for foo")));
+        update.time = 1714608000;
+        let mut remove = Event::new(Action::Remove, "bar", "official").versions(Some("2.0-1"), None);
+        remove.time = 1714608000;
+
+        History::append(&path, &[install.clone()]).unwrap();
+        History::append(&path, &[update.clone(), remove.clone()]).unwrap();
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"garbage\n").unwrap();
+
+        assert_eq!(History::read(&path, &Filter::default()).unwrap(), vec![install.clone(), update.clone(), remove]);
+        assert_eq!(update.new_version, None);
+        assert_eq!(update.error.as_deref(), Some("makepkg failed for foo"));
+
+        let foo = Filter { package: Some("foo".to_string()), ..Filter::default() };
+        assert_eq!(History::read(&path, &foo).unwrap().len(), 2);
+        let failed = Filter { ok: Some(false), ..Filter::default() };
+        assert_eq!(History::read(&path, &failed).unwrap(), vec![update]);
+        let first_day = Filter { since: Some(1714521600), until: Some(1714608000), ..Filter::default() };
+        assert_eq!(History::read(&path, &first_day).unwrap(), vec![install]);
+
+        assert!(History::read(&tmp.path().join("missing"), &Filter::default()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! Package details for `rauri -Si`, in the style of `pacman -Si`.
+//!
+//! Official packages are read from the sync databases, anything else is looked up in
+//! the AUR. Both are turned into a list of labelled fields, which the renderer prints
+//! either as aligned text or, with `--json`, as one JSON object per package.
+
+use anyhow::Result;
+use colored::Colorize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use crate::aur::{Aur, AurPackage};
+use crate::render::Renderer;
+use crate::syncdb::{SyncDb, SyncPackage};
+use crate::ui::Ui;
+
+const AUR_PACKAGE_URL: &str = "https://aur.archlinux.org/packages";
+
+enum Field {
+    Text(Option<String>),
+    List(Vec<String>),
+    Number(i64),
+    Decimal(f64),
+    /// Unix timestamp.
+    Date(Option<i64>),
+    /// Unix timestamp of when the package was flagged out of date.
+    Flagged(Option<i64>),
+    /// Size in bytes.
+    Size(Option<u64>),
+}
+
+impl Field {
+    fn text(&self) -> String {
+        match self {
+            Field::Text(value) => value.clone().unwrap_or_else(|| "None".to_string()),
+            Field::List(values) if values.is_empty() => "None".to_string(),
+            Field::List(values) => values.join("  "),
+            Field::Number(n) => n.to_string(),
+            Field::Decimal(d) => format!("{:.2}", d),
+            Field::Date(date) => date.map_or_else(|| "None".to_string(), Ui::format_date),
+            Field::Flagged(None) => "No".to_string(),
+            Field::Flagged(Some(since)) => format!("Yes ({})", Ui::format_date(*since)),
+            Field::Size(size) => size.map_or_else(|| "None".to_string(), Ui::format_size),
+        }
+    }
+
+    fn json(&self) -> Value {
+        match self {
+            Field::Text(value) => json!(value),
+            Field::List(values) => json!(values),
+            Field::Number(n) => json!(n),
+            Field::Decimal(d) => json!(d),
+            Field::Date(date) | Field::Flagged(date) => json!(date),
+            Field::Size(size) => json!(size),
+        }
+    }
+}
+
+/// The details of one package: (label, JSON key, value) in display order.
+pub struct Card {
+    fields: Vec<(&'static str, &'static str, Field)>,
+}
+
+impl Card {
+    fn from_sync(pkg: &SyncPackage) -> Self {
+        Card {
+            fields: vec![
+                ("Repository", "repository", Field::Text(Some(pkg.repo.clone()))),
+                ("Name", "name", Field::Text(Some(pkg.name.clone()))),
+                ("Version", "version", Field::Text(Some(pkg.version.clone()))),
+                ("Description", "description", Field::Text(pkg.description.clone())),
+                ("URL", "url", Field::Text(pkg.url.clone())),
+                ("Licenses", "licenses", Field::List(pkg.license.clone())),
+                ("Groups", "groups", Field::List(pkg.groups.clone())),
+                ("Provides", "provides", Field::List(pkg.provides.clone())),
+                ("Depends On", "depends", Field::List(pkg.depends.clone())),
+                ("Optional Deps", "optdepends", Field::List(pkg.optdepends.clone())),
+                ("Conflicts With", "conflicts", Field::List(pkg.conflicts.clone())),
+                ("Replaces", "replaces", Field::List(pkg.replaces.clone())),
+                ("Installed Size", "installed_size", Field::Size(pkg.installed_size)),
+                ("Packager", "packager", Field::Text(pkg.packager.clone())),
+                ("Build Date", "build_date", Field::Date(pkg.build_date)),
+            ],
+        }
+    }
+
+    fn from_aur(pkg: &AurPackage) -> Self {
+        Card {
+            fields: vec![
+                ("Repository", "repository", Field::Text(Some("aur".to_string()))),
+                ("Name", "name", Field::Text(Some(pkg.name.clone()))),
+                ("Package Base", "package_base", Field::Text(Some(pkg.package_base.clone()))),
+                ("Version", "version", Field::Text(Some(pkg.version.clone()))),
+                ("Description", "description", Field::Text(pkg.description.clone())),
+                ("URL", "url", Field::Text(pkg.url.clone())),
+                ("AUR URL", "aur_url", Field::Text(Some(format!("{}/{}", AUR_PACKAGE_URL, pkg.name)))),
+                ("Keywords", "keywords", Field::List(pkg.keywords.clone())),
+                ("Licenses", "licenses", Field::List(pkg.license.clone())),
+                ("Provides", "provides", Field::List(pkg.provides.clone())),
+                ("Depends On", "depends", Field::List(pkg.depends.clone())),
+                ("Make Deps", "makedepends", Field::List(pkg.make_depends.clone())),
+                ("Check Deps", "checkdepends", Field::List(pkg.check_depends.clone())),
+                ("Optional Deps", "optdepends", Field::List(pkg.opt_depends.clone())),
+                ("Conflicts With", "conflicts", Field::List(pkg.conflicts.clone())),
+                ("Replaces", "replaces", Field::List(pkg.replaces.clone())),
+                ("Maintainer", "maintainer", Field::Text(pkg.maintainer.clone())),
+                ("Votes", "votes", Field::Number(pkg.votes)),
+                ("Popularity", "popularity", Field::Decimal(pkg.popularity)),
+                ("First Submitted", "first_submitted", Field::Date(Some(pkg.first_submitted))),
+                ("Last Modified", "last_modified", Field::Date(Some(pkg.last_modified))),
+                ("Out-of-date", "out_of_date", Field::Flagged(pkg.out_of_date)),
+            ],
+        }
+    }
+
+    /// "Label : value" lines with the colons aligned, like pacman.
+    pub fn render(&self, is_tty: bool) -> String {
+        let width = self.fields.iter().map(|(label, _, _)| label.len()).max().unwrap_or(0);
+        self.fields.iter()
+            .map(|(label, _, field)| {
+                let label = format!("{:width$}", label, width = width);
+                let label = if is_tty { label.bold().to_string() } else { label };
+                format!("{} : {}", label, field.text())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn to_json(&self) -> Value {
+        let object: Map<String, Value> = self.fields.iter()
+            .map(|(_, key, field)| (key.to_string(), field.json()))
+            .collect();
+        Value::Object(object)
+    }
+}
+
+pub struct Info;
+
+impl Info {
+    /// Print the details of every package in `names`. Packages that can't be found are
+    /// reported and make the command fail after the others have been shown.
+    pub fn show(names: &[&str], renderer: &dyn Renderer) -> Result<()> {
+        let sync_db = SyncDb::load_or_warn();
+
+        // Everything not in the repositories is looked up in one AUR request.
+        let aur_names: Vec<&str> = names.iter().copied().filter(|name| sync_db.get(name).is_none()).collect();
+        let aur_packages = match aur_names.is_empty() {
+            true => Ok(HashMap::new()),
+            false => Aur::get_packages_info(&aur_names),
+        };
+
+        let mut cards = Vec::new();
+        let mut missing = 0;
+        for name in names {
+            let card = match (sync_db.get(name), &aur_packages) {
+                (Some(pkg), _) => Card::from_sync(pkg),
+                (None, Ok(packages)) => match packages.get(*name) {
+                    Some(pkg) => Card::from_aur(pkg),
+                    None => {
+                        Ui::error(&format!("{}: Package not found", name));
+                        missing += 1;
+                        continue;
+                    }
+                },
+                (None, Err(e)) => {
+                    Ui::error(&format!("{}: {:#}", name, e));
+                    missing += 1;
+                    continue;
+                }
+            };
+            cards.push(card);
+        }
+
+        renderer.info(&cards);
+
+        if missing > 0 {
+            anyhow::bail!("{} of {} packages not found", missing, names.len());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aur_package() -> AurPackage {
+        AurPackage {
+            name: "foo-git".to_string(),
+            package_base: "foo".to_string(),
+            version: "1.0.r3-1".to_string(),
+            description: Some("Foo from git".to_string()),
+            votes: 12,
+            popularity: 0.25,
+            out_of_date: Some(0),
+            last_modified: 86400,
+            depends: vec!["glibc".to_string(), "zlib>=1.3".to_string()],
+            make_depends: vec!["git".to_string()],
+            provides: vec!["foo".to_string()],
+            conflicts: vec!["foo".to_string()],
+            license: vec!["MIT".to_string()],
+            ..AurPackage::default()
+        }
+    }
+
+    #[test]
+    fn renders_aur_package() {
+        let card = Card::from_aur(&aur_package());
+        let text = card.render(false);
+
+        assert!(text.contains("Name            : foo-git\n"));
+        assert!(text.contains("Depends On      : glibc  zlib>=1.3\n"));
+        assert!(text.contains("Check Deps      : None\n"));
+        assert!(text.contains("AUR URL         : https://aur.archlinux.org/packages/foo-git\n"));
+        assert!(text.contains("Last Modified   : 1970-01-02 00:00 UTC\n"));
+        assert!(text.ends_with("Out-of-date     : Yes (1970-01-01 00:00 UTC)"));
+
+        let json = card.to_json();
+        assert_eq!(json["repository"], "aur");
+        assert_eq!(json["url"], Value::Null);
+        assert_eq!(json["votes"], 12);
+        assert_eq!(json["makedepends"], json!(["git"]));
+        assert_eq!(json["out_of_date"], 0);
+    }
+}
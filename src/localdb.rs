@@ -0,0 +1,199 @@
+//! Reader for pacman's local database, the record of what is installed.
+//!
+//! Every installed package has a directory `<dbpath>/local/<name>-<version>/` whose
+//! `desc` file lists its metadata as `%KEY%` headers, each followed by one value per
+//! line and terminated by an empty line. Reading these once is much cheaper than
+//! asking `pacman -Q` about every package separately.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// pacman's default DBPath.
+pub const DEFAULT_DB_PATH: &str = "/var/lib/pacman";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallReason {
+    Explicit,
+    Dependency,
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalPackage {
+    pub name: String,
+    pub version: String,
+    /// The pkgbase the package was built from; older packages may not record it.
+    pub base: Option<String>,
+    /// Unix timestamp.
+    pub install_date: Option<i64>,
+    pub reason: InstallReason,
+}
+
+/// The `%KEY%` sections of a desc file. Shared with the sync databases, which use
+/// the same format.
+pub struct DescFields<'a> {
+    fields: BTreeMap<&'a str, Vec<&'a str>>,
+}
+
+impl<'a> DescFields<'a> {
+    pub fn parse(content: &'a str) -> Self {
+        let mut fields: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        let mut current: Option<&str> = None;
+
+        for line in content.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                current = None;
+            } else if let Some(key) = line.strip_prefix('%').and_then(|l| l.strip_suffix('%')) {
+                current = Some(key);
+                fields.entry(key).or_default();
+            } else if let Some(key) = current {
+                fields.entry(key).or_default().push(line);
+            }
+        }
+
+        DescFields { fields }
+    }
+
+    pub fn first(&self, key: &str) -> Option<String> {
+        self.fields.get(key).and_then(|v| v.first()).map(|s| s.to_string())
+    }
+
+    pub fn all(&self, key: &str) -> Vec<String> {
+        self.fields.get(key)
+            .map(|v| v.iter().map(|s| s.to_string()).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl LocalPackage {
+    /// Parse the `desc` file of one installed package.
+    pub fn parse(content: &str) -> Result<Self> {
+        let desc = DescFields::parse(content);
+        let first = |key: &str| desc.first(key);
+
+        Ok(LocalPackage {
+            name: first("NAME").context("desc has no %NAME%")?,
+            version: first("VERSION").context("desc has no %VERSION%")?,
+            base: first("BASE"),
+            install_date: first("INSTALLDATE").and_then(|d| d.parse().ok()),
+            // A missing %REASON% means the package was installed explicitly.
+            reason: match first("REASON").as_deref() {
+                Some("1") => InstallReason::Dependency,
+                _ => InstallReason::Explicit,
+            },
+        })
+    }
+}
+
+/// Snapshot of all installed packages, keyed by name.
+#[derive(Debug, Default)]
+pub struct LocalDb {
+    packages: BTreeMap<String, LocalPackage>,
+}
+
+impl LocalDb {
+    /// Read the local database under pacman's default DBPath.
+    pub fn load() -> Result<Self> {
+        Self::load_from(Path::new(DEFAULT_DB_PATH))
+    }
+
+    /// Read the local database under `db_path`, the directory that contains `local/`.
+    pub fn load_from(db_path: &Path) -> Result<Self> {
+        let local_dir = db_path.join("local");
+        let entries = fs::read_dir(&local_dir)
+            .with_context(|| format!("Failed to read pacman database: {}", local_dir.display()))?;
+
+        let mut packages = BTreeMap::new();
+        for entry in entries.flatten() {
+            // Besides package directories local/ only holds the ALPM_DB_VERSION file.
+            if !entry.path().is_dir() {
+                continue;
+            }
+
+            let desc = entry.path().join("desc");
+            let content = fs::read_to_string(&desc)
+                .with_context(|| format!("Failed to read {}", desc.display()))?;
+            let package = LocalPackage::parse(&content)
+                .with_context(|| format!("Failed to parse {}", desc.display()))?;
+            packages.insert(package.name.clone(), package);
+        }
+
+        Ok(LocalDb { packages })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LocalPackage> {
+        self.packages.get(name)
+    }
+
+    pub fn is_installed(&self, name: &str) -> bool {
+        self.packages.contains_key(name)
+    }
+
+    pub fn version(&self, name: &str) -> Option<&str> {
+        self.get(name).map(|p| p.version.as_str())
+    }
+
+    pub fn reason(&self, name: &str) -> Option<InstallReason> {
+        self.get(name).map(|p| p.reason)
+    }
+
+    /// All installed packages, sorted by name.
+    pub fn packages(&self) -> impl Iterator<Item = &LocalPackage> {
+        self.packages.values()
+    }
+
+    /// Installed packages that no sync repository provides, like `pacman -Qm`.
+    pub fn foreign<'a>(&'a self, sync_names: &'a HashSet<String>) -> impl Iterator<Item = &'a LocalPackage> {
+        self.packages().filter(move |p| !sync_names.contains(&p.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_package(db_path: &Path, desc: &str) {
+        let package = LocalPackage::parse(desc).unwrap();
+        let dir = db_path.join("local").join(format!("{}-{}", package.name, package.version));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("desc"), desc).unwrap();
+    }
+
+    #[test]
+    fn reads_fixture_database() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join("local")).unwrap();
+        fs::write(tmp.path().join("local").join("ALPM_DB_VERSION"), "9\n").unwrap();
+
+        add_package(tmp.path(), "%NAME%\nfoo-docs\n\n%VERSION%\n1:1.2-3\n\n%BASE%\nfoo\n\n\
+            %DESC%\nFoo documentation\n\n%INSTALLDATE%\n1700000000\n\n\
+            %DEPENDS%\nglibc\nzlib>=1.3\n\n");
+        add_package(tmp.path(), "%NAME%\nglibc\n\n%VERSION%\n2.40-1\n\n%REASON%\n1\n\n");
+
+        let db = LocalDb::load_from(tmp.path()).unwrap();
+
+        let names: Vec<&str> = db.packages().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["foo-docs", "glibc"]);
+        assert!(!db.is_installed("foo"));
+        assert_eq!(db.version("foo-docs"), Some("1:1.2-3"));
+        assert_eq!(db.reason("foo-docs"), Some(InstallReason::Explicit));
+        assert_eq!(db.reason("glibc"), Some(InstallReason::Dependency));
+
+        let docs = db.get("foo-docs").unwrap();
+        assert_eq!(docs.base.as_deref(), Some("foo"));
+        assert_eq!(docs.install_date, Some(1700000000));
+
+        let sync: HashSet<String> = ["glibc".to_string()].into_iter().collect();
+        let foreign: Vec<&str> = db.foreign(&sync).map(|p| p.name.as_str()).collect();
+        assert_eq!(foreign, vec!["foo-docs"]);
+    }
+
+    #[test]
+    fn rejects_desc_without_version() {
+        assert!(LocalPackage::parse("%NAME%\nfoo\n\n").is_err());
+    }
+}
@@ -7,12 +7,39 @@ mod tracker;
 mod aur;
 mod ui;
 mod package;
-
+mod deps;
+mod vercmp;
+mod devel;
+mod srcinfo;
+mod review;
+mod localdb;
+mod syncdb;
+mod cli;
+mod completions;
+mod info;
+mod render;
+mod plan;
+mod runner;
+mod adopt;
+mod manifest;
+mod history;
+mod rollback;
+mod paths;
+
+use adopt::Adopt;
+use cli::{Cli, Command};
 use config::Config;
+use history::History;
+use info::Info;
+use manifest::Manifest;
 use package::PackageManager;
+use rollback::Rollback;
 use aur::Aur;
 use ui::Ui;
 
+/// Exit code of `--check-updates` when everything is up to date, as with checkupdates.
+const EXIT_NO_UPDATES: i32 = 2;
+
 fn main() {
     if let Err(e) = run() {
         Ui::error(&format!("Error: {}", e));
@@ -23,49 +50,28 @@ fn main() {
 fn run() -> Result<()> {
     let args: Vec<String> = env::args().skip(1).collect();
 
-    // Extract global flags before any other processing
-    let clear_aur_path = args.contains(&"-C".to_string());
-    let skip_aur = args.contains(&"--skip-aur".to_string());
-    let mut args: Vec<String> = args.into_iter()
-        .filter(|a| a != "-C" && a != "--skip-aur")
-        .collect();
-
-    // Check for -P flag (set AUR path)
-    let mut aur_path: Option<PathBuf> = None;
-    if let Some(p_index) = args.iter().position(|a| a == "-P") {
-        if p_index + 1 >= args.len() {
-            Ui::error("Please provide a path after -P");
-            std::process::exit(1);
-        }
-
-        let potential_path = &args[p_index + 1];
-
-        if potential_path.starts_with('-') {
-            Ui::error(&format!(
-                "Invalid path: '{}'. Did you mean to use a command? Use -P with a valid directory path.",
-                potential_path
-            ));
-            std::process::exit(1);
-        }
-
-        aur_path = Some(PathBuf::from(potential_path));
-        args.remove(p_index);
-        args.remove(p_index);
-    }
-
     let mut config = Config::load()
         .context("Failed to load config")?;
+    let cli = Cli::parse(&args, &config)
+        // Report usage errors as JSON too when it was asked for.
+        .inspect_err(|_| Ui::set_json(args.iter().any(|a| a == "--json")))?;
+    Ui::set_json(cli.json);
+    let renderer = render::renderer(cli.json);
+
+    // Shells run these on startup and on every tab press, so they must never prompt for
+    // the first-run setup or create directories.
+    match &cli.command {
+        Command::Completions(shell) => {
+            print!("{}", completions::script(*shell, &config));
+            return Ok(());
+        }
+        Command::CompletionNames { what, prefix } => return completions::print_names(*what, prefix),
+        _ => {}
+    }
 
     // Handle -P flag: set AUR path and exit
-    if let Some(path) = aur_path {
-        let mut expanded_path = path;
-
-        if let Some(path_str) = expanded_path.to_str() {
-            if path_str.starts_with('~') {
-                let home = dirs::home_dir().expect("Failed to get home directory");
-                expanded_path = home.join(path_str[1..].trim_start_matches('/'));
-            }
-        }
+    if let Some(path) = cli.set_path {
+        let mut expanded_path = paths::expand_home(path);
 
         expanded_path = expanded_path.canonicalize()
             .or_else(|_| {
@@ -106,64 +112,63 @@ fn run() -> Result<()> {
         Ui::success(&format!("Configuration saved to {}", Config::config_path().display()));
     }
 
-    config.ensure_download_dir()
-        .context("Failed to create download directory")?;
-
-    if clear_aur_path {
-        PackageManager::clear_aur_path()?;
+    if cli.devel {
+        config.devel = true;
     }
+    config.dry_run = cli.dry_run;
 
-    if args.is_empty() || args[0] == "-h" || args[0] == "--help" {
-        Ui::print_help(&config);
-        return Ok(());
-    }
+    config.ensure_download_dir()
+        .context("Failed to create download directory")?;
 
-    // AUR URL shortcut
-    if !args.is_empty() && Aur::is_aur_url(&args[0]) {
-        handle_aur_url(&args[0], &config)?;
-        return Ok(());
+    if cli.clear_aur_path {
+        PackageManager::clear_aur_path(&config)?;
     }
 
-    let command = args[0].clone();
-    let has_pkg = args.len() >= 2;
-
-    if config.is_search_all_cmd(&command) {
-        if !has_pkg {
-            Ui::error("Please provide a package name to search");
-            std::process::exit(1);
+    match cli.command {
+        Command::Help(kind) => cli::print_help(kind, &config),
+        Command::AurUrl(url) => handle_aur_url(&url, &config)?,
+        // Picking results needs the numbered text list.
+        Command::SearchAndInstall(query) if cli.json => {
+            let limit = if config.search_limit == 0 { None } else { Some(config.search_limit) };
+            PackageManager::search(&query, limit, &config, renderer.as_ref())?;
         }
-        PackageManager::search(&args[1], None)?;
-    } else if config.is_search_cmd(&command) {
-        if !has_pkg {
-            Ui::error("Please provide a package name to search");
-            std::process::exit(1);
+        Command::SearchAndInstall(query) => PackageManager::search_and_install(&query, &config)?,
+        Command::Search { query, all } => {
+            // 0 in config means unlimited
+            let limit = if all || config.search_limit == 0 { None } else { Some(config.search_limit) };
+            PackageManager::search(&query, limit, &config, renderer.as_ref())?;
         }
-        // 0 in config means unlimited
-        let limit = if config.search_limit == 0 { None } else { Some(config.search_limit) };
-        PackageManager::search(&args[1], limit)?;
-    } else if config.is_install_cmd(&command) && has_pkg {
-        PackageManager::install(&args[1], &config)?;
-    } else if config.is_install_cmd(&command) || command == "--update-aur" {
-        // -S with no package arg, or explicit --update-aur
-        PackageManager::update_aur_only(&config)?;
-    } else if config.is_update_all_cmd(&command) {
-        PackageManager::update_system(&config, skip_aur)?;
-    } else if config.is_remove_cmd(&command) {
-        if !has_pkg {
-            Ui::error("Please provide a package name to remove");
-            std::process::exit(1);
+        Command::Install { targets, needed } => {
+            let targets: Vec<&str> = targets.iter().map(|t| t.as_str()).collect();
+            PackageManager::install(&targets, needed, &config)?;
         }
-        PackageManager::remove(&args[1], Some(&config))?;
-    } else if config.is_list_all_cmd(&command) {
-        PackageManager::list_all()?;
-    } else if config.is_list_cmd(&command) {
-        PackageManager::list_installed()?;
-    } else if config.is_update_mirrors_cmd(&command) {
-        PackageManager::update_mirrors(&config)?;
-    } else {
-        Ui::error(&format!("Unknown command: {}", command));
-        Ui::print_help(&config);
-        std::process::exit(1);
+        Command::Info { targets } => {
+            let targets: Vec<&str> = targets.iter().map(|t| t.as_str()).collect();
+            Info::show(&targets, renderer.as_ref())?;
+        }
+        Command::UpdateAur => PackageManager::update_aur_only(&config)?,
+        Command::UpdateAll { skip_aur, needed } => PackageManager::update_system(&config, skip_aur, needed)?,
+        Command::CheckUpdates => {
+            if !PackageManager::check_updates(&config, renderer.as_ref())? {
+                std::process::exit(EXIT_NO_UPDATES);
+            }
+        }
+        Command::Remove { targets } => {
+            let targets: Vec<&str> = targets.iter().map(|t| t.as_str()).collect();
+            PackageManager::remove(&targets, Some(&config))?;
+        }
+        Command::List { all: true } => PackageManager::list_all(&config, renderer.as_ref())?,
+        Command::List { all: false } => PackageManager::list_installed(&config, renderer.as_ref())?,
+        Command::UpdateMirrors => PackageManager::update_mirrors(&config)?,
+        Command::Adopt { targets, all } => {
+            let targets: Vec<&str> = targets.iter().map(|t| t.as_str()).collect();
+            Adopt::run(&targets, all, &config)?;
+        }
+        Command::Export { path, versions } => Manifest::export(path.as_deref(), versions, &config)?,
+        Command::Import { path, diff } => Manifest::import(&path, diff, &config)?,
+        Command::History(filter) => History::show(&filter, renderer.as_ref())?,
+        Command::Rollback { package, version } => Rollback::run(&package, version.as_deref(), &config)?,
+        Command::Completions(_) | Command::CompletionNames { .. } => unreachable!("handled before setup"),
     }
 
     Ok(())
@@ -171,18 +176,5 @@ fn run() -> Result<()> {
 
 fn handle_aur_url(url: &str, config: &Config) -> Result<()> {
     let package_name = Aur::extract_package_name(url)?;
-    let package_dir = Aur::clone_repo(url, &config.download_dir)?;
-    let actual_package_name = Aur::build_and_install(&package_dir, &package_name)?;
-
-    if let Err(e) = tracker::PackageTracker::add(&actual_package_name) {
-        Ui::warning(&format!("Failed to track package: {}", e));
-    }
-
-    if actual_package_name != package_name {
-        Ui::success(&format!("Installed {} successfully", package_name));
-    } else {
-        Ui::success(&format!("Installed {} successfully", actual_package_name));
-    }
-
-    Ok(())
+    PackageManager::install_aur(&[&package_name], config)
 }
@@ -0,0 +1,229 @@
+//! `rauri export` and `rauri import`: a manifest of the packages that make up a system,
+//! for setting up other machines the same way.
+//!
+//! The manifest lists the explicitly installed packages of the sync repositories and the
+//! explicitly installed AUR packages rauri tracks. Dependencies are left out, installing
+//! the packages brings them back. With `--versions` entries also record the installed
+//! version and, for AUR packages, the AUR commit they were built from; import always
+//! installs the current versions and only reports where they differ.
+
+use anyhow::{Context, Result};
+use atty::Stream;
+use colored::{Color, Colorize};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::localdb::{InstallReason, LocalDb};
+use crate::package::PackageManager;
+use crate::syncdb::SyncDb;
+use crate::tracker::PackageTracker;
+use crate::ui::Ui;
+
+/// Version of the manifest layout written by this build.
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    /// The AUR commit the package was built from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    commit: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    version: u32,
+    #[serde(default)]
+    official: Vec<Entry>,
+    #[serde(default)]
+    aur: Vec<Entry>,
+}
+
+/// How a manifest differs from the installed system.
+#[derive(Debug, Default, PartialEq)]
+struct Diff<'a> {
+    /// Not installed: (entry, "official" or "aur").
+    add: Vec<(&'a Entry, &'static str)>,
+    /// Explicitly installed but not in the manifest.
+    remove: Vec<(&'a Entry, &'static str)>,
+    /// Installed at another version than the manifest records: (entry, installed version).
+    changed: Vec<(&'a Entry, &'a str)>,
+}
+
+impl Diff<'_> {
+    fn print(&self) {
+        if self.add.is_empty() && self.remove.is_empty() && self.changed.is_empty() {
+            Ui::success("The system matches the manifest");
+            return;
+        }
+
+        let is_tty = atty::is(Stream::Stdout);
+        let sign = |sign: &str, color: Color| if is_tty { sign.color(color).to_string() } else { sign.to_string() };
+
+        for (entry, source) in &self.add {
+            println!("{} {} ({}) would be installed", sign("+", Color::Green), entry.name, source);
+        }
+        for (entry, source) in &self.remove {
+            println!("{} {} ({}) is installed but not in the manifest", sign("-", Color::Red), entry.name, source);
+        }
+        for (entry, installed) in &self.changed {
+            println!("{} {} {} is installed, the manifest has {}", sign("~", Color::Yellow),
+                entry.name, installed, entry.version.as_deref().unwrap_or_default());
+        }
+    }
+}
+
+impl Manifest {
+    fn parse(content: &str) -> Result<Self> {
+        let manifest: Manifest = toml::from_str(content)?;
+        if manifest.version > MANIFEST_VERSION {
+            anyhow::bail!("written by a newer rauri (version {}, this one reads up to {})",
+                manifest.version, MANIFEST_VERSION);
+        }
+        Ok(manifest)
+    }
+
+    /// The explicitly installed packages of this system, from the data of `list_all`.
+    fn current(config: &Config, local_db: &LocalDb, sync_db: &SyncDb, versions: bool) -> Self {
+        let tracked = PackageTracker::entries().unwrap_or_default();
+        let mut manifest = Manifest { version: MANIFEST_VERSION, official: Vec::new(), aur: Vec::new() };
+
+        for pkg in PackageManager::installed_packages(config, local_db, sync_db) {
+            if local_db.reason(&pkg.name) != Some(InstallReason::Explicit) || pkg.source == "local" {
+                continue;
+            }
+            let entry = Entry {
+                version: versions.then(|| pkg.version.clone()),
+                commit: tracked.get(&pkg.name).and_then(|t| t.commit.clone()).filter(|_| versions),
+                name: pkg.name,
+            };
+            if pkg.source == "aur" {
+                manifest.aur.push(entry);
+            } else {
+                manifest.official.push(Entry { commit: None, ..entry });
+            }
+        }
+
+        manifest
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (&Entry, &'static str)> {
+        self.official.iter().map(|e| (e, "official"))
+            .chain(self.aur.iter().map(|e| (e, "aur")))
+    }
+
+    fn len(&self) -> usize {
+        self.official.len() + self.aur.len()
+    }
+
+    /// What installing `self` would change, compared with the `current` manifest.
+    fn diff<'a>(&'a self, current: &'a Manifest, local_db: &'a LocalDb) -> Diff<'a> {
+        let wanted: HashSet<&str> = self.entries().map(|(e, _)| e.name.as_str()).collect();
+        let mut diff = Diff::default();
+
+        for (entry, source) in self.entries() {
+            match local_db.version(&entry.name) {
+                None => diff.add.push((entry, source)),
+                Some(installed) if entry.version.as_deref().is_some_and(|v| v != installed) => {
+                    diff.changed.push((entry, installed));
+                }
+                Some(_) => {}
+            }
+        }
+        diff.remove = current.entries()
+            .filter(|(e, _)| !wanted.contains(e.name.as_str()))
+            .collect();
+
+        diff
+    }
+
+    /// Write the manifest of this system to `path`, or to stdout.
+    pub fn export(path: Option<&Path>, versions: bool, config: &Config) -> Result<()> {
+        let local_db = LocalDb::load()?;
+        let sync_db = SyncDb::load_or_warn();
+        let manifest = Self::current(config, &local_db, &sync_db, versions);
+
+        let content = toml::to_string_pretty(&manifest).context("Failed to serialize manifest")?;
+        match path {
+            Some(path) => {
+                fs::write(path, content)
+                    .with_context(|| format!("Failed to write manifest: {}", path.display()))?;
+                Ui::success(&format!("Wrote {} packages to {}", manifest.len(), path.display()));
+            }
+            None => print!("{}", content),
+        }
+        Ok(())
+    }
+
+    /// Install the packages of the manifest at `path` that are missing, or with
+    /// `diff_only` just show how the manifest differs from this system.
+    pub fn import(path: &Path, diff_only: bool, config: &Config) -> Result<()> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest: {}", path.display()))?;
+        let wanted = Self::parse(&content)
+            .with_context(|| format!("Failed to parse manifest: {}", path.display()))?;
+
+        let local_db = LocalDb::load()?;
+        let sync_db = SyncDb::load_or_warn();
+        let current = Self::current(config, &local_db, &sync_db, false);
+        let diff = wanted.diff(&current, &local_db);
+
+        if diff_only {
+            diff.print();
+            return Ok(());
+        }
+
+        if diff.add.is_empty() {
+            Ui::info("Every package of the manifest is installed");
+            return Ok(());
+        }
+
+        // install() finds out again which are official, in case a package moved.
+        let missing: Vec<&str> = diff.add.iter().map(|(e, _)| e.name.as_str()).collect();
+        Ui::info(&format!("Installing {} missing packages from {}", missing.len(), path.display()));
+        PackageManager::install(&missing, false, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, version: Option<&str>) -> Entry {
+        Entry { name: name.to_string(), version: version.map(|v| v.to_string()), commit: None }
+    }
+
+    #[test]
+    fn diffs_manifest_against_system() {
+        let tmp = tempfile::tempdir().unwrap();
+        for (name, version) in [("git", "2.45-1"), ("vim", "9.1-1"), ("foo", "1.0-1")] {
+            let dir = tmp.path().join("local").join(format!("{}-{}", name, version));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("desc"), format!("%NAME%\n{}\n\n%VERSION%\n{}\n\n", name, version)).unwrap();
+        }
+        let local_db = LocalDb::load_from(tmp.path()).unwrap();
+
+        let wanted = Manifest::parse("version = 1\n\n\
+            [[official]]\nname = \"git\"\nversion = \"2.46-1\"\n\n\
+            [[official]]\nname = \"htop\"\n\n\
+            [[aur]]\nname = \"foo\"\nversion = \"1.0-1\"\ncommit = \"abc123\"\n").unwrap();
+        let current = Manifest {
+            version: MANIFEST_VERSION,
+            official: vec![entry("git", None), entry("vim", None)],
+            aur: vec![entry("foo", None)],
+        };
+
+        let diff = wanted.diff(&current, &local_db);
+        assert_eq!(diff.add, vec![(&wanted.official[1], "official")]);
+        assert_eq!(diff.remove, vec![(&current.official[1], "official")]);
+        assert_eq!(diff.changed, vec![(&wanted.official[0], "2.45-1")]);
+        assert_eq!(wanted.aur[0].commit.as_deref(), Some("abc123"));
+
+        assert!(Manifest::parse("version = 2\n").is_err());
+    }
+}
@@ -1,76 +1,289 @@
 use anyhow::{Context, Result};
-use std::collections::HashSet;
-use std::path::PathBuf;
-use std::process::Command;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::time::Instant;
 
-use crate::aur::Aur;
+use crate::aur::{Aur, AurPackage};
 use crate::config::Config;
-use crate::tracker::PackageTracker;
-use crate::ui::{Ui, Colors};
+use crate::deps::{dep_name, DependencyResolver};
+use crate::devel::{self, is_devel, DevelStore};
+use crate::history::{Action, Event, History};
+use crate::localdb::{InstallReason, LocalDb};
+use crate::plan::{self, Plan, Step};
+use crate::render::{self, official_header, InstalledPackage, Renderer, SearchResults, TextRenderer};
+use crate::review::Review;
+use crate::runner::Cmd;
+use crate::syncdb::{SyncDb, SyncPackage};
+use crate::srcinfo::Srcinfo;
+use crate::tracker::{self, PackageTracker};
+use crate::ui::Ui;
+use crate::vercmp::vercmp;
 use atty::Stream;
 use colored::Colorize;
 
 pub struct PackageManager;
 
-/// Parse pacman -Ss output into (header, description) pairs.
-fn parse_pacman_output(stdout: &str) -> Vec<(String, String)> {
-    let lines: Vec<&str> = stdout.lines().collect();
-    let mut packages = Vec::new();
-    let mut i = 0;
+/// Lower = higher priority. Sorts exact name match → starts with → contains → description match.
+fn match_priority(name: &str, query: &str) -> u8 {
+    let q = query.to_lowercase();
+    let name = name.to_lowercase();
+    if name == q { 0 }
+    else if name.starts_with(&q) { 1 }
+    else if name.contains(&q) { 2 }
+    else { 3 }
+}
+
+/// One entry of the merged interactive search list.
+enum SearchResult<'a> {
+    Official(&'a SyncPackage),
+    Aur(&'a AurPackage),
+}
 
-    while i < lines.len() {
-        let line = lines[i];
-        if line.trim().is_empty() {
-            i += 1;
-            continue;
+impl SearchResult<'_> {
+    fn name(&self) -> &str {
+        match self {
+            SearchResult::Official(pkg) => &pkg.name,
+            SearchResult::Aur(pkg) => &pkg.name,
         }
+    }
 
-        if !line.starts_with(' ') {
-            let header = line.to_string();
-            let desc = if i + 1 < lines.len() && lines[i + 1].starts_with(' ') {
-                i += 1;
-                lines[i].to_string()
-            } else {
-                String::new()
-            };
-            packages.push((header, desc));
+    fn description(&self) -> Option<&str> {
+        match self {
+            SearchResult::Official(pkg) => pkg.description.as_deref(),
+            SearchResult::Aur(pkg) => pkg.description.as_deref(),
         }
+    }
 
-        i += 1;
+    fn header(&self, local_db: Option<&LocalDb>) -> String {
+        match self {
+            SearchResult::Official(pkg) => official_header(pkg, local_db),
+            SearchResult::Aur(pkg) => {
+                let mut header = format!("aur/{} {} (+{} {:.2})", pkg.name, pkg.version, pkg.votes, pkg.popularity);
+                if pkg.out_of_date.is_some() {
+                    header.push_str(" (out of date)");
+                }
+                if local_db.is_some_and(|db| db.is_installed(&pkg.name)) {
+                    header.push_str(" [installed]");
+                }
+                header
+            }
+        }
     }
+}
 
-    packages
+/// One pkgbase to build, with the pkgnames to install from it.
+struct AurBuild {
+    pkgbase: String,
+    pkgs: Vec<AurPackage>,
+    /// Official packages needed to build and run it.
+    repo_deps: Vec<String>,
+    /// Built for a requested package rather than as a dependency.
+    is_target: bool,
+    dir: PathBuf,
+    /// Already cloned while planning.
+    cloned: bool,
 }
 
-/// Extract bare package name from a pacman header line like "extra/code 1.99.0-1 [installed]".
-fn pkg_name_from_header(header: &str) -> &str {
-    let first = header.split_whitespace().next().unwrap_or("");
-    if let Some(pos) = first.rfind('/') { &first[pos + 1..] } else { first }
+impl AurBuild {
+    fn pkgnames(&self) -> Vec<String> {
+        self.pkgs.iter().map(|p| p.name.clone()).collect()
+    }
+
+    /// The official packages `builds` need, each once.
+    fn repo_deps<'a>(builds: impl IntoIterator<Item = &'a AurBuild>) -> Vec<String> {
+        let mut deps: Vec<String> = builds.into_iter().flat_map(|b| b.repo_deps.iter().cloned()).collect();
+        deps.sort();
+        deps.dedup();
+        deps
+    }
 }
 
-/// Lower = higher priority. Sorts name matches above description matches.
-fn official_match_priority(header: &str, query: &str) -> u8 {
-    let q = query.to_lowercase();
-    let name = pkg_name_from_header(header).to_lowercase();
-    if name == q { 0 }
-    else if name.starts_with(&q) { 1 }
-    else if name.contains(&q) { 2 }
-    else { 3 } // description match (caller already filtered by pacman)
+/// Everything an AUR install will do, worked out before the first build.
+struct AurPlan {
+    /// The requested packages (and split package members picked for them).
+    selected: Vec<String>,
+    /// In build order.
+    builds: Vec<AurBuild>,
 }
 
-fn aur_match_priority(pkg: &crate::aur::AurPackage, query: &str) -> u8 {
-    let q = query.to_lowercase();
-    let name = pkg.name.to_lowercase();
-    if name == q { 0 }
-    else if name.starts_with(&q) { 1 }
-    else if name.contains(&q) { 2 }
-    else { 3 }
+impl AurPlan {
+    fn steps(&self, config: &Config) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for build in &self.builds {
+            if !build.cloned {
+                steps.push(Step::Clone { url: Aur::clone_url(&build.pkgbase), dir: build.dir.clone() });
+            }
+        }
+        for build in &self.builds {
+            if config.review {
+                steps.push(Step::Review { dir: build.dir.clone() });
+            }
+        }
+
+        let repo_deps = AurBuild::repo_deps(&self.builds);
+        if !repo_deps.is_empty() {
+            let deps: Vec<&str> = repo_deps.iter().map(|d| d.as_str()).collect();
+            steps.push(plan::pacman(&["-S", "--needed", "--asdeps", "--noconfirm"], &deps));
+        }
+        for build in &self.builds {
+            let pkgnames = build.pkgnames();
+            steps.push(Step::Build { dir: build.dir.clone(), pkgnames: pkgnames.clone(), as_dep: !build.is_target });
+            steps.push(Step::Track { pkgbase: build.pkgbase.clone(), pkgnames });
+            if config.devel && is_devel(&build.pkgbase) {
+                steps.push(Step::RecordDevel(build.pkgbase.clone()));
+            }
+        }
+        steps
+    }
+}
+
+/// A pkgbase `update_aur_only` rebuilds.
+struct Rebuild {
+    pkgbase: String,
+    pkgnames: Vec<String>,
+    /// The installed version of each of `pkgnames`.
+    installed: Vec<String>,
+    /// Shown before the build, e.g. "Updating foo from 1.0-1 to 1.1-1...".
+    reason: String,
+}
+
+/// Everything an AUR update will do.
+struct AurUpdate {
+    /// Tracked packages that are no longer installed.
+    stale: Vec<String>,
+    /// Number of pkgbases compared with the AUR.
+    checked: usize,
+    rebuilds: Vec<Rebuild>,
+    /// Devel packages' upstream commits, with newly seen packages recorded.
+    devel_store: DevelStore,
+}
+
+impl AurUpdate {
+    fn steps(&self, config: &Config) -> Vec<Step> {
+        let mut steps: Vec<Step> = self.stale.iter().map(|name| Step::Untrack(name.clone())).collect();
+
+        for rebuild in &self.rebuilds {
+            let dir = config.download_dir.join(&rebuild.pkgbase);
+            steps.push(Step::Clone { url: Aur::clone_url(&rebuild.pkgbase), dir: dir.clone() });
+            if config.review {
+                steps.push(Step::Review { dir: dir.clone() });
+            }
+            steps.push(Step::Build { dir, pkgnames: rebuild.pkgnames.clone(), as_dep: false });
+            steps.push(Step::Track { pkgbase: rebuild.pkgbase.clone(), pkgnames: rebuild.pkgnames.clone() });
+            if config.devel && is_devel(&rebuild.pkgbase) {
+                steps.push(Step::RecordDevel(rebuild.pkgbase.clone()));
+            }
+        }
+        steps
+    }
+}
+
+/// One package `remove` uninstalls.
+struct Removal {
+    name: String,
+    /// Its installed -debug package.
+    debug: Option<String>,
+    pkgbase: String,
+    version: String,
+    /// "aur" if rauri tracks it, "official" otherwise.
+    source: &'static str,
+}
+
+/// What is left to do for a pkgbase once its last package is removed.
+struct Cleanup {
+    pkgbase: String,
+    /// The checkout, if there is one.
+    folder: Option<PathBuf>,
+    forget_devel: bool,
+}
+
+/// Everything `remove` will do.
+#[derive(Default)]
+struct RemovalPlan {
+    packages: Vec<Removal>,
+    /// pkgbases whose checkout stays, with their remaining packages.
+    kept: Vec<(String, Vec<String>)>,
+    cleanups: Vec<Cleanup>,
+}
+
+impl RemovalPlan {
+    fn steps(&self) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for removal in &self.packages {
+            steps.push(plan::pacman(&["-R", "--noconfirm"], &[&removal.name]));
+            if let Some(debug) = &removal.debug {
+                steps.push(plan::pacman(&["-R", "--noconfirm"], &[debug]));
+            }
+            steps.push(Step::Untrack(removal.name.clone()));
+        }
+        for cleanup in &self.cleanups {
+            if cleanup.forget_devel {
+                steps.push(Step::ForgetDevel(cleanup.pkgbase.clone()));
+            }
+            if let Some(folder) = &cleanup.folder {
+                steps.push(Step::RemoveDir(folder.clone()));
+            }
+        }
+        steps
+    }
+}
+
+/// Outcome of every target of a multi-package command, reported once all were tried.
+#[derive(Default)]
+struct Summary {
+    results: Vec<(String, std::result::Result<(), String>)>,
+}
+
+impl Summary {
+    fn succeeded(&mut self, name: &str) {
+        self.results.push((name.to_string(), Ok(())));
+    }
+
+    fn failed(&mut self, name: &str, error: &anyhow::Error) {
+        self.results.push((name.to_string(), Err(format!("{:#}", error))));
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.results.iter().any(|(n, _)| n == name)
+    }
+
+    /// Print the per-target summary and fail if any target failed. A single target
+    /// just fails with its own error.
+    fn finish(self, action: &str) -> Result<()> {
+        let failures: Vec<&String> = self.results.iter()
+            .filter_map(|(_, result)| result.as_ref().err())
+            .collect();
+
+        if self.results.len() == 1 {
+            return match failures.first() {
+                Some(error) => Err(anyhow::anyhow!("{}", error)),
+                None => Ok(()),
+            };
+        }
+
+        if self.results.len() > 1 {
+            Ui::info("Summary:");
+            for (name, result) in &self.results {
+                match result {
+                    Ok(()) => Ui::success(name),
+                    Err(e) => Ui::error(&format!("{}: {}", name, e)),
+                }
+            }
+        }
+
+        if !failures.is_empty() {
+            anyhow::bail!("Failed to {} {} of {} packages", action, failures.len(), self.results.len());
+        }
+
+        Ok(())
+    }
 }
 
 impl PackageManager {
     /// Search AUR and official repos. Pass limit=None to show all results.
-    pub fn search(query: &str, limit: Option<usize>) -> Result<()> {
+    pub fn search(query: &str, limit: Option<usize>, config: &Config, renderer: &dyn Renderer) -> Result<()> {
         let mut aur_packages = match Aur::search(query) {
             Ok(packages) => packages,
             Err(e) => {
@@ -80,184 +293,508 @@ impl PackageManager {
         };
 
         // Sort AUR results: exact name match → starts with → contains → description match
-        aur_packages.sort_by_key(|pkg| aur_match_priority(pkg, query));
+        aur_packages.sort_by_key(|pkg| match_priority(&pkg.name, query));
 
-        let is_tty = atty::is(Stream::Stdout);
+        // Search official repos
+        let sync_db = SyncDb::load_or_warn();
+        let local_db = LocalDb::load().ok();
+
+        let mut official_packages = sync_db.search(query);
+        official_packages.sort_by_key(|pkg| match_priority(&pkg.name, query));
+
+        renderer.search(&SearchResults {
+            aur: &aur_packages,
+            official: &official_packages,
+            limit,
+            local_db: local_db.as_ref(),
+            download_dir: &config.download_dir,
+        });
+
+        Ok(())
+    }
 
-        if !aur_packages.is_empty() {
-            let total = aur_packages.len();
-            let shown = limit.map_or(total, |n| n.min(total));
+    /// List AUR and official matches for `query` as one numbered list and install the
+    /// entries the user picks, e.g. "1 3-5 ^4". Without a terminal to ask on this is
+    /// a plain search.
+    pub fn search_and_install(query: &str, config: &Config) -> Result<()> {
+        // 0 in config means unlimited
+        let limit = if config.search_limit == 0 { None } else { Some(config.search_limit) };
+
+        if !atty::is(Stream::Stdin) {
+            return Self::search(query, limit, config, &TextRenderer);
+        }
 
+        let mut aur_packages = Aur::search(query).unwrap_or_else(|e| {
+            Ui::warning(&format!("Failed to search AUR: {}", e));
+            Vec::new()
+        });
+        aur_packages.sort_by_key(|pkg| match_priority(&pkg.name, query));
+
+        let sync_db = SyncDb::load_or_warn();
+        let mut official_packages = sync_db.search(query);
+        official_packages.sort_by_key(|pkg| match_priority(&pkg.name, query));
+
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut results: Vec<SearchResult> = official_packages.into_iter().take(limit)
+            .map(SearchResult::Official)
+            .chain(aur_packages.iter().take(limit).map(SearchResult::Aur))
+            .collect();
+        // Stable, so official packages come first among equally good matches.
+        results.sort_by_key(|r| match_priority(r.name(), query));
+
+        if results.is_empty() {
+            Ui::warning("No packages found");
+            return Ok(());
+        }
+
+        let local_db = LocalDb::load().ok();
+        let is_tty = atty::is(Stream::Stdout);
+        for (i, result) in results.iter().enumerate() {
+            let header = result.header(local_db.as_ref());
             if is_tty {
-                println!("\n{}", "AUR Packages:".cyan().bold());
+                println!("{} {}", format!("{:>3}", i + 1).magenta().bold(), header.bold());
             } else {
-                println!("\nAUR Packages:");
-            }
-
-            for pkg in aur_packages.iter().take(shown) {
-                let desc = pkg.description.as_ref()
-                    .map(|d| format!(" - {}", d))
-                    .unwrap_or_default();
-                if is_tty {
-                    println!("  {}{}{} {}({}){}{}",
-                        Colors::BOLD, pkg.name.yellow(), Colors::RESET,
-                        Colors::DIM, pkg.version, Colors::RESET, desc);
-                } else {
-                    println!("  {} ({}){}", pkg.name, pkg.version, desc);
+                println!("{:>3} {}", i + 1, header);
+            }
+            if let Some(desc) = result.description() {
+                println!("      {}", desc);
+            }
+        }
+
+        let answer = Ui::prompt("Packages to install (e.g. \"1 3-5 ^4\"):")?;
+        if answer.is_empty() {
+            Ui::info("Nothing selected");
+            return Ok(());
+        }
+
+        let mut official: Vec<&str> = Vec::new();
+        let mut aur: Vec<&str> = Vec::new();
+        for i in Ui::parse_selection(&answer, results.len())? {
+            match &results[i] {
+                SearchResult::Official(pkg) => official.push(&pkg.name),
+                SearchResult::Aur(pkg) => aur.push(&pkg.name),
+            }
+        }
+
+        Self::install_targets(&official, &aur, config)
+    }
+
+    /// Install packages from the official repositories where they exist there, and
+    /// build the rest from the AUR. With `needed`, targets that are already installed
+    /// and up to date are skipped.
+    pub fn install(package_names: &[&str], needed: bool, config: &Config) -> Result<()> {
+        let sync_db = SyncDb::load_or_warn();
+
+        // A package of a repository rauri can't read is still not an AUR package.
+        let (mut official, mut aur): (Vec<&str>, Vec<&str>) = package_names.iter()
+            .partition(|name| sync_db.satisfier(name).is_some() || sync_db.unread_satisfier(name).is_some());
+
+        if needed {
+            let local_db = LocalDb::load()?;
+            let aur_info = if aur.is_empty() { HashMap::new() } else { Aur::get_packages_info(&aur)? };
+
+            let up_to_date = |name: &&str| {
+                let latest = sync_db.satisfier(name)
+                    .map(|p| (p.name.as_str(), p.version.as_str()))
+                    .or_else(|| aur_info.get(*name).map(|p| (p.name.as_str(), p.version.as_str())));
+                let current = latest.is_some_and(|(name, version)| local_db.version(name)
+                    .is_some_and(|installed| vercmp(installed, version) != Ordering::Less));
+                if current {
+                    Ui::info(&format!("{} is up to date -- skipping", name));
                 }
+                !current
+            };
+            official.retain(up_to_date);
+            aur.retain(up_to_date);
+        }
+
+        if !aur.is_empty() {
+            Ui::info(&format!("Installing {} from AUR...", aur.join(", ")));
+        }
+        Self::install_targets(&official, &aur, config)
+    }
+
+    /// Install official targets in one pacman transaction, then build the AUR targets
+    /// together. Every target is attempted; failures are reported in a summary at the end.
+    /// With --dry-run the AUR targets are planned and everything is printed instead.
+    fn install_targets(official: &[&str], aur: &[&str], config: &Config) -> Result<()> {
+        let mut summary = Summary::default();
+
+        if config.dry_run {
+            let mut plan = Plan::default();
+            if !official.is_empty() {
+                plan.push(plan::pacman(&["-S", "--noconfirm"], official));
+            }
+            if let Some(aur_plan) = Self::plan_aur_targets(aur, config, &mut summary) {
+                plan.extend(aur_plan.steps(config));
+            }
+            plan.print();
+            return summary.finish("install");
+        }
+
+        // Official packages first, AUR packages may depend on them.
+        if !official.is_empty() {
+            let before = LocalDb::load().ok();
+            let started = Instant::now();
+            let result = Self::install_official(official);
+
+            let after = LocalDb::load().ok();
+            let events: Vec<Event> = official.iter().map(|name| {
+                Event::new(Action::Install, name, "official")
+                    .versions(before.as_ref().and_then(|db| db.version(name)),
+                        after.as_ref().and_then(|db| db.version(name)))
+                    .took(started.elapsed())
+                    .result(&result)
+            }).collect();
+            History::record(&events);
+
+            match result {
+                Ok(()) => official.iter().for_each(|name| summary.succeeded(name)),
+                Err(e) => official.iter().for_each(|name| summary.failed(name, &e)),
             }
+        }
+        if let Some(aur_plan) = Self::plan_aur_targets(aur, config, &mut summary) {
+            Self::execute_aur_plan(aur_plan, config, &mut summary);
+        }
 
-            if let Some(n) = limit {
-                if total > n {
-                    if is_tty {
-                        println!("  {}... and {} more AUR results. Use --search-all to see all{}",
-                            Colors::DIM, total - n, Colors::RESET);
-                    } else {
-                        println!("  ... and {} more AUR results. Use --search-all to see all", total - n);
+        summary.finish("install")
+    }
+
+    /// Install official packages with a single pacman transaction.
+    pub fn install_official(package_names: &[&str]) -> Result<()> {
+        Ui::info(&format!("Installing {} from official repositories...", package_names.join(", ")));
+
+        let install_result = Cmd::new("sudo")
+            .arg("pacman")
+            .arg("-S")
+            .arg("--noconfirm")
+            .args(package_names)
+            .status()
+            .context("Failed to execute pacman install")?;
+
+        if !install_result.success() {
+            anyhow::bail!("Installation failed");
+        }
+
+        Ui::success(&format!("Installed {} successfully", package_names.join(", ")));
+        Ok(())
+    }
+
+    /// Resolve the AUR dependency tree of `package_names`, install their official
+    /// dependencies, then build and install the AUR packages in dependency order.
+    /// If a package belongs to a split pkgbase the user picks which of its
+    /// packages to install.
+    pub fn install_aur(package_names: &[&str], config: &Config) -> Result<()> {
+        Self::install_targets(&[], package_names, config)
+    }
+
+    /// Plan the AUR targets, recording targets that can't be installed in `summary`.
+    /// None if there is nothing left to build.
+    fn plan_aur_targets(package_names: &[&str], config: &Config, summary: &mut Summary) -> Option<AurPlan> {
+        if package_names.is_empty() {
+            return None;
+        }
+
+        match Self::try_plan_aur_targets(package_names, config, summary) {
+            Ok(plan) => plan,
+            Err(e) => {
+                // Failed before anything was built, e.g. while resolving dependencies.
+                for package_name in package_names {
+                    if !summary.contains(package_name) {
+                        summary.failed(package_name, &e);
                     }
                 }
+                None
             }
         }
+    }
 
-        // Search official repos
-        let official_result = Command::new("pacman")
-            .arg("-Ss")
-            .arg(query)
-            .output();
+    fn try_plan_aur_targets(package_names: &[&str], config: &Config, summary: &mut Summary) -> Result<Option<AurPlan>> {
+        let mut found = Aur::get_packages_info(package_names)?;
 
-        let has_official_results = match &official_result {
-            Ok(output) if output.status.success() && !output.stdout.is_empty() => {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                let mut packages = parse_pacman_output(&stdout);
+        // pkgbase → checkout of every requested package, None until it is cloned.
+        let mut target_dirs: HashMap<String, Option<PathBuf>> = HashMap::new();
+        let mut selected: Vec<String> = Vec::new();
+        for package_name in package_names {
+            if selected.iter().any(|s| s == package_name) {
+                continue;
+            }
 
-                // Sort: name matches first, description matches last
-                packages.sort_by_key(|(header, _)| official_match_priority(header, query));
+            let target = match found.remove(*package_name) {
+                Some(target) => target,
+                None => {
+                    summary.failed(package_name,
+                        &anyhow::anyhow!("Package not found in official repositories or AUR: {}", package_name));
+                    continue;
+                }
+            };
+            if target_dirs.contains_key(&target.package_base) {
+                selected.push(package_name.to_string());
+                continue;
+            }
 
-                let total = packages.len();
-                let shown = limit.map_or(total, |n| n.min(total));
+            // Choosing split package members needs the .SRCINFO, so targets are cloned
+            // while planning. A dry run doesn't clone and installs just the requested package.
+            if config.dry_run {
+                target_dirs.insert(target.package_base.clone(), None);
+                selected.push(package_name.to_string());
+                continue;
+            }
 
-                if shown > 0 {
-                    if is_tty {
-                        println!("\n{}", "Official Repository Packages:".cyan().bold());
+            let members = Aur::clone_repo(&Aur::clone_url(&target.package_base), &config.download_dir)
+                .and_then(|target_dir| {
+                    let srcinfo = Srcinfo::from_dir(&target_dir)?;
+                    let members = if srcinfo.packages.len() > 1 {
+                        Self::choose_split_members(&srcinfo, package_name)?
                     } else {
-                        println!("\nOfficial Repository Packages:");
-                    }
-
-                    for (header, desc) in packages.iter().take(shown) {
-                        if is_tty {
-                            println!("{}", header.bold());
-                        } else {
-                            println!("{}", header);
-                        }
-                        if !desc.is_empty() {
-                            println!("{}", desc);
+                        vec![package_name.to_string()]
+                    };
+                    target_dirs.insert(target.package_base.clone(), Some(target_dir));
+                    Ok(members)
+                });
+
+            match members {
+                Ok(members) => {
+                    for member in members {
+                        if !selected.contains(&member) {
+                            selected.push(member);
                         }
                     }
+                }
+                Err(e) => summary.failed(package_name, &e),
+            }
+        }
 
-                    if let Some(n) = limit {
-                        if total > n {
-                            if is_tty {
-                                println!("  {}... and {} more official results. Use --search-all to see all{}",
-                                    Colors::DIM, total - n, Colors::RESET);
-                            } else {
-                                println!("  ... and {} more official results. Use --search-all to see all", total - n);
-                            }
-                        }
-                    }
+        if selected.is_empty() {
+            return Ok(None);
+        }
 
-                    true
-                } else {
-                    false
+        Ui::info("Resolving dependencies...");
+        let targets: Vec<&str> = selected.iter().map(|s| s.as_str()).collect();
+        let resolved = DependencyResolver::resolve(&targets)?;
+
+        let aur_deps: Vec<&str> = resolved.aur_order.iter()
+            .map(|p| p.name.as_str())
+            .filter(|n| !selected.iter().any(|s| s == n))
+            .collect();
+        if !aur_deps.is_empty() {
+            Ui::info(&format!("AUR dependencies (build order): {}", aur_deps.join(" → ")));
+        }
+
+        for pkg in &resolved.aur_order {
+            if pkg.maintainer.is_none() {
+                Ui::warning(&format!("{} is orphaned (no maintainer)", pkg.name));
+            }
+            if pkg.out_of_date.is_some() {
+                Ui::warning(&format!("{} has been flagged out of date on the AUR", pkg.name));
+            }
+        }
+
+        // Build each pkgbase once, installing all the pkgnames needed from it.
+        let mut builds: Vec<AurBuild> = Vec::new();
+        for pkg in resolved.aur_order {
+            match builds.iter_mut().find(|b| b.pkgbase == pkg.package_base) {
+                Some(build) => build.pkgs.push(pkg),
+                None => {
+                    let checkout = target_dirs.get(&pkg.package_base).cloned().flatten();
+                    builds.push(AurBuild {
+                        repo_deps: Vec::new(),
+                        is_target: target_dirs.contains_key(&pkg.package_base),
+                        dir: config.download_dir.join(&pkg.package_base),
+                        cloned: checkout.is_some(),
+                        pkgbase: pkg.package_base.clone(),
+                        pkgs: vec![pkg],
+                    });
                 }
             }
-            _ => false,
-        };
+        }
 
-        if aur_packages.is_empty() && !has_official_results {
-            Ui::warning("No packages found");
+        for build in &mut builds {
+            build.repo_deps = build.pkgs.iter()
+                .flat_map(|pkg| resolved.repo_deps.get(&pkg.name).into_iter().flatten().cloned())
+                .collect();
+            build.repo_deps.sort();
+            build.repo_deps.dedup();
         }
 
-        Ok(())
+        Ok(Some(AurPlan { selected, builds }))
     }
 
-    pub fn install(package_name: &str, config: &Config) -> Result<()> {
-        let check_result = Command::new("pacman")
-            .arg("-Si")
-            .arg(package_name)
-            .output();
-
-        match check_result {
-            Ok(output) if output.status.success() => {
-                Ui::info(&format!("Installing {} from official repositories...", package_name));
-
-                let install_result = Command::new("sudo")
-                    .arg("pacman")
-                    .arg("-S")
-                    .arg("--noconfirm")
-                    .arg(package_name)
-                    .status()
-                    .context("Failed to execute pacman install")?;
-
-                if install_result.success() {
-                    Ui::success(&format!("Installed {} successfully", package_name));
-                } else {
-                    anyhow::bail!("Installation failed");
+    /// Fetch and review every pkgbase of `plan`, install the repository dependencies of
+    /// the ones that passed review, then build and install them in order. A failed build
+    /// fails the targets that need it.
+    fn execute_aur_plan(plan: AurPlan, config: &Config, summary: &mut Summary) {
+        let AurPlan { selected, builds } = plan;
+
+        // Fetch and review everything before the system is changed.
+        let mut checkouts: Vec<Result<PathBuf>> = Vec::new();
+        for build in &builds {
+            let package_dir = match build.cloned {
+                true => Ok(build.dir.clone()),
+                false => Aur::clone_repo(&Aur::clone_url(&build.pkgbase), &config.download_dir),
+            };
+            checkouts.push(package_dir.and_then(|dir| {
+                Review::review(&dir, &build.pkgbase, config)?;
+                Ok(dir)
+            }));
+        }
+
+        let reviewed = builds.iter().zip(&checkouts).filter(|(_, checkout)| checkout.is_ok());
+        let repo_deps = AurBuild::repo_deps(reviewed.map(|(build, _)| build));
+        if !repo_deps.is_empty() {
+            Ui::info(&format!("Installing repository dependencies: {}", repo_deps.join(" ")));
+
+            let status = Cmd::new("sudo")
+                .arg("pacman")
+                .arg("-S")
+                .arg("--needed")
+                .arg("--asdeps")
+                .arg("--noconfirm")
+                .args(&repo_deps)
+                .status()
+                .context("Failed to execute pacman install")
+                .and_then(|status| match status.success() {
+                    true => Ok(()),
+                    false => Err(anyhow::anyhow!("Failed to install repository dependencies")),
+                });
+
+            if let Err(e) = status {
+                for (build, checkout) in builds.iter().zip(checkouts.iter_mut()) {
+                    if checkout.is_ok() && !build.repo_deps.is_empty() {
+                        *checkout = Err(anyhow::anyhow!("{:#}", e));
+                    }
                 }
             }
-            _ => {
-                Ui::info(&format!("Installing {} from AUR...", package_name));
+        }
 
-                let aur_url = format!("https://aur.archlinux.org/{}.git", package_name);
-                let package_dir = Aur::clone_repo(&aur_url, &config.download_dir)?;
-                let actual_package_name = Aur::build_and_install(&package_dir, package_name)?;
+        // Packages that could not be installed; everything depending on them is skipped.
+        let mut failed: HashSet<String> = HashSet::new();
+        let local_db = LocalDb::load().ok();
+        for (build, checkout) in builds.iter().zip(checkouts) {
+            let pkgnames = build.pkgnames();
+            let started = Instant::now();
+
+            let missing_dep = build.pkgs.iter()
+                .flat_map(|p| p.depends.iter().chain(&p.make_depends).chain(&p.check_depends))
+                .map(|d| dep_name(d))
+                .find(|d| failed.contains(*d));
+
+            let result = match (missing_dep, checkout) {
+                (Some(dep), _) => Err(anyhow::anyhow!("dependency {} was not installed", dep)),
+                (None, Err(e)) => Err(e),
+                (None, Ok(package_dir)) => {
+                    if !build.is_target {
+                        Ui::info(&format!("Building dependency {}...", pkgnames.join(", ")));
+                    }
+                    Aur::build_and_install(&package_dir, &pkgnames, !build.is_target, config.keep_builds)
+                        .map(|version| (package_dir, version))
+                }
+            };
 
-                if let Err(e) = PackageTracker::add(&actual_package_name) {
-                    Ui::warning(&format!("Failed to track package: {}", e));
+            let events: Vec<Event> = pkgnames.iter().map(|name| {
+                Event::new(Action::Install, name, "aur")
+                    .versions(local_db.as_ref().and_then(|db| db.version(name)),
+                        result.as_ref().ok().map(|(_, version)| version.as_str()))
+                    .took(started.elapsed())
+                    .result(&result)
+            }).collect();
+            History::record(&events);
+
+            let (package_dir, version) = match result {
+                Ok(built) => built,
+                Err(e) => {
+                    // Targets report their errors in the summary; dependencies only here.
+                    if !build.is_target {
+                        Ui::warning(&format!("Failed to install dependency {}: {:#}", pkgnames.join(", "), e));
+                    }
+                    for name in &pkgnames {
+                        if selected.contains(name) {
+                            summary.failed(name, &e);
+                        }
+                    }
+                    failed.extend(pkgnames);
+                    continue;
                 }
+            };
 
-                if actual_package_name != package_name {
-                    Ui::success(&format!("Installed {} successfully", package_name));
-                } else {
-                    Ui::success(&format!("Installed {} successfully", actual_package_name));
+            let reason = if build.is_target { InstallReason::Explicit } else { InstallReason::Dependency };
+            let record = tracker::Build { version, commit: Aur::head_commit(&package_dir), reason: Some(reason) };
+            if let Err(e) = PackageTracker::add_base(&build.pkgbase, &pkgnames, &record) {
+                Ui::warning(&format!("Failed to track package: {}", e));
+            }
+
+            if config.devel && is_devel(&build.pkgbase) {
+                match DevelStore::load() {
+                    Ok(mut store) => {
+                        Self::record_devel(&mut store, &build.pkgbase, &package_dir);
+                        if let Err(e) = store.save() {
+                            Ui::warning(&format!("Failed to save devel package data: {}", e));
+                        }
+                    }
+                    Err(e) => Ui::warning(&format!("Not recording upstream commits of {}: {:#}", build.pkgbase, e)),
                 }
             }
-        }
 
-        Ok(())
+            for name in pkgnames.iter().filter(|n| selected.contains(n)) {
+                summary.succeeded(name);
+            }
+            if build.is_target {
+                Ui::success(&format!("Installed {} successfully", pkgnames.join(", ")));
+            }
+        }
     }
 
-    pub fn cleanup_tracking() -> Result<()> {
-        let tracked_packages = PackageTracker::load().unwrap_or_default();
+    /// Let the user pick which packages of a split pkgbase to install. Defaults to
+    /// just the requested one, which is also what non-interactive runs get.
+    fn choose_split_members(srcinfo: &Srcinfo, requested: &str) -> Result<Vec<String>> {
+        let pkgnames: Vec<&str> = srcinfo.pkgnames().collect();
 
-        if tracked_packages.is_empty() {
-            return Ok(());
+        if !atty::is(Stream::Stdin) {
+            return Ok(vec![requested.to_string()]);
+        }
+
+        Ui::info(&format!("{} is built from {}, which provides {} packages:",
+            requested, srcinfo.base.pkgbase, pkgnames.len()));
+        for (i, name) in pkgnames.iter().enumerate() {
+            println!("  {}) {}", i + 1, name);
         }
 
-        let mut packages_to_remove = Vec::new();
-        for package_name in &tracked_packages {
-            let check_result = Command::new("pacman")
-                .arg("-Q")
-                .arg(package_name)
-                .output();
+        let answer = Ui::prompt(&format!(
+            "Packages to install (e.g. \"1 3\", \"2-4 ^3\", \"a\" for all) [default: {}]:", requested))?;
 
-            if let Ok(output) = check_result {
-                if !output.status.success() {
-                    packages_to_remove.push(package_name.clone());
-                }
-            }
+        if answer.is_empty() {
+            return Ok(vec![requested.to_string()]);
+        }
+        if answer.eq_ignore_ascii_case("a") || answer.eq_ignore_ascii_case("all") {
+            return Ok(pkgnames.iter().map(|n| n.to_string()).collect());
         }
 
-        for package_name in &packages_to_remove {
+        Ok(Ui::parse_selection(&answer, pkgnames.len())?
+            .into_iter()
+            .map(|i| pkgnames[i].to_string())
+            .collect())
+    }
+
+    /// Tracked packages that are no longer installed.
+    fn stale_tracking(local_db: &LocalDb) -> Vec<String> {
+        let mut stale: Vec<String> = PackageTracker::load().unwrap_or_default()
+            .into_iter()
+            .filter(|name| !local_db.is_installed(name))
+            .collect();
+        stale.sort();
+        stale
+    }
+
+    fn untrack_stale(stale: &[String]) {
+        for package_name in stale {
             if let Err(e) = PackageTracker::remove(package_name) {
                 Ui::warning(&format!("Failed to remove {} from tracking: {}", package_name, e));
             }
         }
 
-        if !packages_to_remove.is_empty() {
-            Ui::info(&format!("Cleaned up {} uninstalled package(s) from tracking", packages_to_remove.len()));
+        if !stale.is_empty() {
+            Ui::info(&format!("Cleaned up {} uninstalled package(s) from tracking", stale.len()));
         }
-
-        Ok(())
     }
 
     /// Run reflector to update the mirrorlist. Skipped if config.update_mirrors is false
@@ -267,7 +804,7 @@ impl PackageManager {
             return Ok(());
         }
 
-        let available = Command::new("which")
+        let available = Cmd::new("which")
             .arg("reflector")
             .output()
             .map(|o| o.status.success())
@@ -279,7 +816,7 @@ impl PackageManager {
         }
 
         Ui::info("Updating mirrorlist with reflector...");
-        let status = Command::new("sudo")
+        let status = Cmd::new("sudo")
             .arg("reflector")
             .arg("--latest")
             .arg("20")
@@ -299,13 +836,46 @@ impl PackageManager {
         Ok(())
     }
 
+    /// Rebuild the AUR packages that have a newer version (or, with devel, new upstream
+    /// commits). With --dry-run only the planned actions are printed.
     pub fn update_aur_only(config: &Config) -> Result<()> {
-        Self::cleanup_tracking()?;
+        let update = Self::plan_aur_update(config)?;
+
+        if config.dry_run {
+            let mut plan = Plan::default();
+            plan.extend(update.steps(config));
+            plan.print();
+            return Ok(());
+        }
+
+        // Keep the names offered by shell completion current.
+        Aur::refresh_package_names();
+        Self::execute_aur_update(update, config)
+    }
+
+    fn plan_aur_update(config: &Config) -> Result<AurUpdate> {
+        // The store is saved after the update, so one that can't be read must not be
+        // replaced by an empty one.
+        let devel_store = if config.devel {
+            DevelStore::load().context("Failed to load devel package data")?
+        } else {
+            DevelStore::default()
+        };
+
+        let local_db = LocalDb::load()?;
+        let stale = Self::stale_tracking(&local_db);
 
-        let tracked_packages = PackageTracker::load().unwrap_or_default();
+        let tracked_packages = PackageTracker::entries().unwrap_or_default();
 
         let mut base_packages = HashSet::new();
-        for pkg in &tracked_packages {
+        for (pkg, entry) in tracked_packages.iter().filter(|(p, _)| !stale.contains(p)) {
+            if entry.ignored {
+                continue;
+            }
+            if entry.pinned {
+                Ui::info(&format!("{} is pinned, skipping", pkg));
+                continue;
+            }
             if pkg.ends_with("-debug") {
                 let base_name = pkg.strip_suffix("-debug").unwrap_or(pkg);
                 base_packages.insert(base_name.to_string());
@@ -314,60 +884,273 @@ impl PackageManager {
             }
         }
 
+        let mut update = AurUpdate { stale, checked: 0, rebuilds: Vec::new(), devel_store };
         if base_packages.is_empty() {
             Ui::info("No AUR packages tracked by rauri to update.");
-            return Ok(());
+            return Ok(update);
         }
 
+        let mut installed: Vec<(String, String)> = Vec::new();
         for package_name in &base_packages {
-            let installed_result = Command::new("pacman")
-                .arg("-Q")
-                .arg(package_name)
-                .output();
-
-            match installed_result {
-                Ok(output) if output.status.success() => {
-                    let installed_info = String::from_utf8_lossy(&output.stdout);
-                    let installed_version = installed_info.trim().split_whitespace().nth(1)
-                        .unwrap_or("");
-
-                    match Aur::get_package_info(package_name) {
-                        Ok(aur_pkg) => {
-                            if installed_version != aur_pkg.version {
-                                Ui::info(&format!("Updating {} from {} to {}...",
-                                    package_name, installed_version, aur_pkg.version));
-
-                                let aur_url = format!("https://aur.archlinux.org/{}.git", package_name);
-                                let package_dir = Aur::clone_repo(&aur_url, &config.download_dir)?;
-                                let actual_package_name = Aur::build_and_install(&package_dir, package_name)?;
-
-                                if let Err(e) = PackageTracker::add(&actual_package_name) {
-                                    Ui::warning(&format!("Failed to track package: {}", e));
-                                }
-                            } else {
-                                Ui::info(&format!("{} is up to date", package_name));
-                            }
-                        }
-                        Err(e) => {
-                            Ui::warning(&format!("Could not check AUR for {}, skipping: {}", package_name, e));
-                        }
-                    }
+            match local_db.version(package_name) {
+                Some(installed_version) => {
+                    installed.push((package_name.clone(), installed_version.to_string()));
                 }
-                _ => {
+                None => {
                     Ui::warning(&format!("Package {} is not installed, skipping", package_name));
                 }
             }
         }
 
-        Ui::success("AUR package updates complete");
+        let names: Vec<&str> = installed.iter().map(|(n, _)| n.as_str()).collect();
+        let aur_info = Aur::get_packages_info(&names)
+            .context("Failed to query AUR for updates")?;
+
+        // Group installed packages by pkgbase so split packages are rebuilt once.
+        let mut by_base: BTreeMap<&str, Vec<(&str, &str)>> = BTreeMap::new();
+        for (package_name, installed_version) in &installed {
+            match aur_info.get(package_name) {
+                Some(aur_pkg) => by_base.entry(aur_pkg.package_base.as_str())
+                    .or_default()
+                    .push((package_name.as_str(), installed_version.as_str())),
+                None => {
+                    Ui::warning(&format!("{} was not found in the AUR, skipping", package_name));
+                }
+            }
+        }
+
+        for (pkgbase, members) in &by_base {
+            update.checked += 1;
+
+            // All packages of a pkgbase share its version.
+            let aur_version = &aur_info[members[0].0].version;
+            let outdated = members.iter()
+                .find(|(_, installed_version)| vercmp(installed_version, aur_version) == Ordering::Less);
+
+            let package_dir = config.download_dir.join(pkgbase);
+            let reason = if let Some((_, installed_version)) = outdated {
+                Some(format!("Updating {} from {} to {}...",
+                    Self::describe_base(pkgbase, members), installed_version, aur_version))
+            } else if config.devel && is_devel(pkgbase) {
+                match Self::devel_changed(&mut update.devel_store, pkgbase, &package_dir) {
+                    Ok(true) => Some(format!("Rebuilding {}: upstream has new commits...",
+                        Self::describe_base(pkgbase, members))),
+                    Ok(false) => {
+                        Self::report_current(members, aur_version);
+                        None
+                    }
+                    Err(e) => {
+                        Ui::warning(&format!("Could not check upstream of {}, skipping: {}", pkgbase, e));
+                        None
+                    }
+                }
+            } else {
+                Self::report_current(members, aur_version);
+                None
+            };
+
+            if let Some(reason) = reason {
+                update.rebuilds.push(Rebuild {
+                    pkgbase: pkgbase.to_string(),
+                    pkgnames: members.iter().map(|(name, _)| name.to_string()).collect(),
+                    installed: members.iter().map(|(_, version)| version.to_string()).collect(),
+                    reason,
+                });
+            }
+        }
+
+        Ok(update)
+    }
+
+    fn execute_aur_update(update: AurUpdate, config: &Config) -> Result<()> {
+        let AurUpdate { stale, checked, rebuilds, mut devel_store } = update;
+        Self::untrack_stale(&stale);
+
+        // A failed rebuild doesn't stop the others, and what was built is still recorded.
+        let mut summary = Summary::default();
+
+        for rebuild in &rebuilds {
+            Ui::info(&rebuild.reason);
+
+            let pkgbase = rebuild.pkgbase.as_str();
+            let started = Instant::now();
+            let built = Self::rebuild(rebuild, config);
+
+            // Rebuilds skipped at review did not change anything.
+            if !matches!(built, Ok(None)) {
+                let version = built.as_ref().ok().and_then(Option::as_ref).map(|(_, version)| version.as_str());
+                let events: Vec<Event> = rebuild.pkgnames.iter().zip(&rebuild.installed).map(|(name, installed)| {
+                    Event::new(Action::Update, name, "aur")
+                        .versions(Some(installed), version)
+                        .took(started.elapsed())
+                        .result(&built)
+                }).collect();
+                History::record(&events);
+            }
+            let (package_dir, version) = match built {
+                Ok(Some(built)) => built,
+                Ok(None) => continue,
+                Err(e) => {
+                    summary.failed(pkgbase, &e);
+                    continue;
+                }
+            };
+            summary.succeeded(pkgbase);
+
+            let record = tracker::Build { version, commit: Aur::head_commit(&package_dir), reason: None };
+            if let Err(e) = PackageTracker::add_base(pkgbase, &rebuild.pkgnames, &record) {
+                Ui::warning(&format!("Failed to track package: {}", e));
+            }
+
+            if config.devel && is_devel(pkgbase) {
+                Self::record_devel(&mut devel_store, pkgbase, &package_dir);
+            }
+        }
+
+        if config.devel {
+            if let Err(e) = devel_store.save() {
+                Ui::warning(&format!("Failed to save devel package data: {}", e));
+            }
+        }
+
+        summary.finish("update")?;
+        if checked > 0 {
+            Ui::success("AUR package updates complete");
+        }
         Ok(())
     }
 
+    /// Fetch, review, build and install one pkgbase of an update. None if the review
+    /// rejected it.
+    fn rebuild(rebuild: &Rebuild, config: &Config) -> Result<Option<(PathBuf, String)>> {
+        let pkgbase = rebuild.pkgbase.as_str();
+        let package_dir = Aur::clone_repo(&Aur::clone_url(pkgbase), &config.download_dir)?;
+        if let Err(e) = Review::review(&package_dir, pkgbase, config) {
+            Ui::warning(&format!("Skipping {}: {}", pkgbase, e));
+            return Ok(None);
+        }
+        let version = Aur::build_and_install(&package_dir, &rebuild.pkgnames, false, config.keep_builds)?;
+        Ok(Some((package_dir, version)))
+    }
+
+    /// "foo" for a plain package, "foo (foo, foo-docs)" for split package members.
+    fn describe_base(pkgbase: &str, members: &[(&str, &str)]) -> String {
+        if members.len() == 1 && members[0].0 == pkgbase {
+            pkgbase.to_string()
+        } else {
+            let names: Vec<&str> = members.iter().map(|(name, _)| *name).collect();
+            format!("{} ({})", pkgbase, names.join(", "))
+        }
+    }
+
+    fn report_current(members: &[(&str, &str)], aur_version: &str) {
+        for (package_name, installed_version) in members {
+            if vercmp(installed_version, aur_version) == Ordering::Greater {
+                Ui::info(&format!("{}: local version {} is newer than AUR version {}",
+                    package_name, installed_version, aur_version));
+            } else {
+                Ui::info(&format!("{} is up to date", package_name));
+            }
+        }
+    }
+
+    /// Compare the upstream commits of a devel package's sources with the ones it was
+    /// last built from. Packages without recorded commits are recorded as current.
+    fn devel_changed(store: &mut DevelStore, package_name: &str, package_dir: &Path) -> Result<bool> {
+        let sources = devel::vcs_sources(&Srcinfo::from_dir(package_dir)?);
+
+        if !store.is_recorded(package_name) {
+            Ui::info(&format!("Recording upstream commits of {} for future devel checks", package_name));
+            store.record(package_name, &sources)?;
+            return Ok(false);
+        }
+
+        store.has_upstream_changes(package_name, &sources)
+    }
+
+    fn record_devel(store: &mut DevelStore, package_name: &str, package_dir: &Path) {
+        let sources = Srcinfo::from_dir(package_dir)
+            .map(|s| devel::vcs_sources(&s))
+            .unwrap_or_default();
+
+        if let Err(e) = store.record(package_name, &sources) {
+            Ui::warning(&format!("Failed to record upstream commits of {}: {}", package_name, e));
+        }
+    }
+
+    /// List pending official and AUR updates without installing anything or needing root.
+    /// Returns whether there are any, for the exit code.
+    pub fn check_updates(config: &Config, renderer: &dyn Renderer) -> Result<bool> {
+        let local_db = LocalDb::load()?;
+        let sync_db = SyncDb::load_refreshed()?;
+
+        let mut updates: Vec<InstalledPackage> = Vec::new();
+        for pkg in local_db.packages() {
+            if let Some(sync_pkg) = sync_db.get(&pkg.name) {
+                updates.push(InstalledPackage {
+                    name: pkg.name.clone(),
+                    pkgbase: pkg.base.clone().unwrap_or_else(|| pkg.name.clone()),
+                    version: pkg.version.clone(),
+                    source: sync_pkg.repo.clone(),
+                    latest_version: Some(sync_pkg.version.clone()),
+                    repo_dir: None,
+                });
+            }
+        }
+
+        // Only packages rauri tracks, the ones -S would update.
+        let tracked: Vec<String> = PackageTracker::entries().unwrap_or_default().into_iter()
+            .filter(|(name, entry)| !entry.pinned && !entry.ignored
+                && local_db.is_installed(name) && sync_db.get(name).is_none())
+            .map(|(name, _)| name)
+            .collect();
+        let names: Vec<&str> = tracked.iter().map(|n| n.as_str()).collect();
+        let aur_info = Aur::get_packages_info(&names)
+            .context("Failed to query AUR for updates")?;
+
+        for name in &tracked {
+            if let (Some(aur_pkg), Some(installed_version)) = (aur_info.get(name), local_db.version(name)) {
+                updates.push(InstalledPackage {
+                    name: name.clone(),
+                    pkgbase: aur_pkg.package_base.clone(),
+                    version: installed_version.to_string(),
+                    source: "aur".to_string(),
+                    latest_version: Some(aur_pkg.version.clone()),
+                    repo_dir: render::repo_dir(&config.download_dir, &aur_pkg.package_base),
+                });
+            }
+        }
+
+        updates.retain(|pkg| pkg.is_outdated());
+        updates.sort_by(|a, b| a.name.cmp(&b.name));
+
+        renderer.updates(&updates);
+        Ok(!updates.is_empty())
+    }
+
     /// Update the full system. Pass skip_aur=true to only update official packages.
-    pub fn update_system(config: &Config, skip_aur: bool) -> Result<()> {
+    pub fn update_system(config: &Config, skip_aur: bool, needed: bool) -> Result<()> {
+        let mut update_args = vec!["-Syu"];
+        if needed {
+            update_args.push("--needed");
+        }
+        update_args.push("--noconfirm");
+
+        if config.dry_run {
+            let mut plan = Plan::default();
+            plan.push(plan::pacman(&["-Syy"], &[]));
+            plan.push(plan::pacman(&update_args, &[]));
+            if !skip_aur {
+                // Planned against the current databases; the sync may bring more updates.
+                plan.extend(Self::plan_aur_update(config)?.steps(config));
+            }
+            plan.print();
+            return Ok(());
+        }
+
         Ui::info("Updating official packages...");
 
-        let sync_result = Command::new("sudo")
+        let sync_result = Cmd::new("sudo")
             .arg("pacman")
             .arg("-Syy")
             .status()
@@ -377,10 +1160,9 @@ impl PackageManager {
             anyhow::bail!("Failed to sync package databases");
         }
 
-        let update_result = Command::new("sudo")
+        let update_result = Cmd::new("sudo")
             .arg("pacman")
-            .arg("-Syu")
-            .arg("--noconfirm")
+            .args(&update_args)
             .status()
             .context("Failed to update system packages")?;
 
@@ -399,7 +1181,9 @@ impl PackageManager {
         Ok(())
     }
 
-    pub fn remove(package_name: &str, config: Option<&Config>) -> Result<()> {
+    /// Remove each of `package_names`, continuing past failures and summarizing at the end.
+    /// Checkouts are deleted once no package of their pkgbase is left.
+    pub fn remove(package_names: &[&str], config: Option<&Config>) -> Result<()> {
         let config_owned;
         let config = match config {
             Some(c) => c,
@@ -409,193 +1193,177 @@ impl PackageManager {
             }
         };
 
-        if package_name.is_empty() {
-            Ui::error("Please provide a package name to remove");
-            return Ok(());
-        }
+        let local_db = LocalDb::load()?;
+        let mut summary = Summary::default();
+        let removal = Self::plan_removal(package_names, config, &local_db, &mut summary);
 
-        let check_exact = Command::new("pacman")
-            .arg("-Q")
-            .arg(package_name)
-            .output();
-
-        let mut actual_package_name = package_name.to_string();
-        let mut repo_name = package_name.to_string();
-
-        match check_exact {
-            Ok(output) if output.status.success() => {
-                actual_package_name = package_name.to_string();
-            }
-            _ => {
-                if let Ok(tracked) = PackageTracker::load() {
-                    for p in &tracked {
-                        let check = Command::new("pacman")
-                            .arg("-Q")
-                            .arg(p)
-                            .output();
-
-                        if let Ok(check_output) = check {
-                            if check_output.status.success() {
-                                if p.contains(package_name) || package_name.contains(p.as_str()) {
-                                    actual_package_name = p.clone();
-                                    if config.download_dir.exists() {
-                                        if let Ok(entries) = fs::read_dir(&config.download_dir) {
-                                            for entry in entries.flatten() {
-                                                let path = entry.path();
-                                                if path.is_dir() {
-                                                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                                                        if name == p || name == package_name {
-                                                            repo_name = name.to_string();
-                                                            break;
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+        if config.dry_run {
+            let mut plan = Plan::default();
+            plan.extend(removal.steps());
+            plan.print();
+        } else {
+            Self::execute_removal(removal, &mut summary);
         }
 
-        let final_check = Command::new("pacman")
-            .arg("-Q")
-            .arg(&actual_package_name)
-            .output();
+        summary.finish("remove")
+    }
+
+    fn plan_removal(package_names: &[&str], config: &Config, local_db: &LocalDb, summary: &mut Summary) -> RemovalPlan {
+        let mut plan = RemovalPlan::default();
 
-        if let Ok(output) = final_check {
-            if !output.status.success() {
-                anyhow::bail!("Package '{}' is not installed", package_name);
+        for package_name in package_names {
+            if package_name.is_empty() {
+                Ui::error("Please provide a package name to remove");
+                continue;
             }
-        } else {
-            anyhow::bail!("Package '{}' is not installed", package_name);
+            if !local_db.is_installed(package_name) {
+                summary.failed(package_name, &anyhow::anyhow!("Package '{}' is not installed", package_name));
+                continue;
+            }
+
+            let debug_package_name = format!("{}-debug", package_name);
+            let tracked = PackageTracker::get(package_name);
+            // Packages tracked before pkgbases were recorded: pacman knows the pkgbase of
+            // recent packages, otherwise find the checkout that builds it.
+            let pkgbase = tracked.as_ref().map(|entry| entry.pkgbase.clone())
+                .or_else(|| local_db.get(package_name).and_then(|p| p.base.clone()))
+                .or_else(|| Self::find_pkgbase(&config.download_dir, package_name))
+                .unwrap_or_else(|| package_name.to_string());
+
+            plan.packages.push(Removal {
+                name: package_name.to_string(),
+                debug: local_db.is_installed(&debug_package_name).then_some(debug_package_name),
+                pkgbase,
+                version: local_db.version(package_name).unwrap_or_default().to_string(),
+                source: if tracked.is_some() { "aur" } else { "official" },
+            });
         }
 
-        let remove_result = Command::new("sudo")
-            .arg("pacman")
-            .arg("-R")
-            .arg("--noconfirm")
-            .arg(&actual_package_name)
-            .status()
-            .context("Failed to execute pacman remove")?;
+        // Keep the build directory while other packages of the same pkgbase are installed.
+        let bases = PackageTracker::load_bases().unwrap_or_default();
+        let devel_store = DevelStore::load().unwrap_or_default();
+        let removed: HashSet<String> = plan.packages.iter()
+            .flat_map(|r| [r.name.clone(), format!("{}-debug", r.name)])
+            .collect();
 
-        if !remove_result.success() {
-            anyhow::bail!("Package removal failed");
+        let mut pkgbases: Vec<&str> = Vec::new();
+        for removal in &plan.packages {
+            if !pkgbases.contains(&removal.pkgbase.as_str()) {
+                pkgbases.push(&removal.pkgbase);
+            }
         }
 
-        let debug_package_name = format!("{}-debug", actual_package_name);
-        let debug_check = Command::new("pacman")
-            .arg("-Q")
-            .arg(&debug_package_name)
-            .output();
-
-        if let Ok(output) = debug_check {
-            if output.status.success() {
-                if let Err(e) = Command::new("sudo")
-                    .arg("pacman")
-                    .arg("-R")
-                    .arg("--noconfirm")
-                    .arg(&debug_package_name)
-                    .status()
-                {
-                    Ui::warning(&format!("Failed to remove debug package {}: {}", debug_package_name, e));
-                }
+        for pkgbase in pkgbases {
+            let remaining: Vec<String> = bases.get(pkgbase)
+                .map(|members| members.iter().filter(|m| !removed.contains(*m)).cloned().collect())
+                .unwrap_or_default();
+
+            if remaining.is_empty() {
+                let folder = config.download_dir.join(pkgbase);
+                plan.cleanups.push(Cleanup {
+                    pkgbase: pkgbase.to_string(),
+                    folder: folder.is_dir().then_some(folder),
+                    forget_devel: devel_store.is_recorded(pkgbase),
+                });
+            } else {
+                plan.kept.push((pkgbase.to_string(), remaining));
             }
         }
 
-        let mut packages_to_untrack = HashSet::new();
-        packages_to_untrack.insert(actual_package_name.clone());
-        packages_to_untrack.insert(package_name.to_string());
+        plan
+    }
 
-        if let Ok(tracked) = PackageTracker::load() {
-            for tracked_pkg in &tracked {
-                if tracked_pkg == &actual_package_name || tracked_pkg == package_name {
-                    packages_to_untrack.insert(tracked_pkg.clone());
+    fn execute_removal(plan: RemovalPlan, summary: &mut Summary) {
+        let mut failed_bases: HashSet<String> = HashSet::new();
+
+        for removal in &plan.packages {
+            let started = Instant::now();
+            let result = Self::remove_one(removal);
+            History::record(&[Event::new(Action::Remove, &removal.name, removal.source)
+                .versions(Some(&removal.version), None)
+                .took(started.elapsed())
+                .result(&result)]);
+
+            match result {
+                Ok(()) => summary.succeeded(&removal.name),
+                Err(e) => {
+                    failed_bases.insert(removal.pkgbase.clone());
+                    summary.failed(&removal.name, &e);
                 }
             }
         }
 
-        for pkg_to_remove in &packages_to_untrack {
-            if let Err(e) = PackageTracker::remove(pkg_to_remove) {
-                Ui::warning(&format!("Failed to untrack package: {}", e));
-            }
+        for (pkgbase, remaining) in &plan.kept {
+            Ui::info(&format!("Keeping {} for the remaining packages: {}", pkgbase, remaining.join(", ")));
         }
 
-        if config.download_dir.exists() {
-            let mut folder_removed = false;
-
-            if let Ok(entries) = fs::read_dir(&config.download_dir) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.is_dir() && !folder_removed {
-                        if path.join("PKGBUILD").exists() {
-                            if let Ok(pkg_files) = fs::read_dir(&path) {
-                                for pkg_file in pkg_files.flatten() {
-                                    let pkg_path = pkg_file.path();
-                                    if pkg_path.extension().and_then(|s| s.to_str()) == Some("zst") {
-                                        if let Some(file_name) = pkg_path.file_name().and_then(|n| n.to_str()) {
-                                            if file_name.ends_with(".pkg.tar.zst") {
-                                                let result = Command::new("pacman")
-                                                    .arg("-Qp")
-                                                    .arg(&pkg_path)
-                                                    .output();
-
-                                                if let Ok(output) = result {
-                                                    if output.status.success() {
-                                                        let stdout = String::from_utf8_lossy(&output.stdout);
-                                                        if let Some(pkg_name_from_file) = stdout.trim().split_whitespace().next() {
-                                                            if pkg_name_from_file == actual_package_name ||
-                                                               pkg_name_from_file == package_name {
-                                                                if let Err(e) = fs::remove_dir_all(&path) {
-                                                                    Ui::warning(&format!("Failed to remove package folder {}: {}", path.display(), e));
-                                                                } else {
-                                                                    folder_removed = true;
-                                                                    break;
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
+        for cleanup in plan.cleanups.iter().filter(|c| !failed_bases.contains(&c.pkgbase)) {
+            if cleanup.forget_devel {
+                if let Ok(mut devel_store) = DevelStore::load() {
+                    devel_store.forget(&cleanup.pkgbase);
+                    if let Err(e) = devel_store.save() {
+                        Ui::warning(&format!("Failed to save devel package data: {}", e));
                     }
                 }
             }
 
-            if !folder_removed {
-                let folder_names_to_try = vec![&repo_name, package_name, &actual_package_name];
-                for folder_name in folder_names_to_try {
-                    let folder_path = config.download_dir.join(folder_name);
-                    if folder_path.exists() && folder_path.is_dir() {
-                        if let Err(e) = fs::remove_dir_all(&folder_path) {
-                            Ui::warning(&format!("Failed to remove package folder {}: {}", folder_path.display(), e));
-                        }
-                        break;
-                    }
+            if let Some(folder_path) = &cleanup.folder {
+                if let Err(e) = fs::remove_dir_all(folder_path) {
+                    Ui::warning(&format!("Failed to remove package folder {}: {}", folder_path.display(), e));
                 }
             }
         }
+    }
 
-        let success_msg = if actual_package_name != package_name {
-            format!("Removed {} (was installed as {})", package_name, actual_package_name)
-        } else {
-            format!("Removed {}", package_name)
-        };
+    fn remove_one(removal: &Removal) -> Result<()> {
+        let remove_result = Cmd::new("sudo")
+            .arg("pacman")
+            .arg("-R")
+            .arg("--noconfirm")
+            .arg(&removal.name)
+            .status()
+            .context("Failed to execute pacman remove")?;
+
+        if !remove_result.success() {
+            anyhow::bail!("Package removal failed");
+        }
+
+        let mut untrack = vec![removal.name.clone()];
+        if let Some(debug_package_name) = &removal.debug {
+            match Cmd::new("sudo")
+                .arg("pacman")
+                .arg("-R")
+                .arg("--noconfirm")
+                .arg(debug_package_name)
+                .status()
+            {
+                Ok(status) if status.success() => untrack.push(debug_package_name.clone()),
+                Ok(status) => Ui::warning(&format!("Failed to remove debug package {}: pacman exited with {}",
+                    debug_package_name, status)),
+                Err(e) => Ui::warning(&format!("Failed to remove debug package {}: {}", debug_package_name, e)),
+            }
+        }
 
-        Ui::success(&format!("{} successfully", success_msg));
+        for name in &untrack {
+            if let Err(e) = PackageTracker::remove(name) {
+                Ui::warning(&format!("Failed to untrack package: {}", e));
+            }
+        }
+
+        Ui::success(&format!("Removed {} successfully", removal.name));
         Ok(())
     }
 
-    pub fn clear_aur_path() -> Result<()> {
-        let config = Config::load()?;
+    /// Look through the checkouts in `download_dir` for the pkgbase that builds `package_name`.
+    fn find_pkgbase(download_dir: &Path, package_name: &str) -> Option<String> {
+        fs::read_dir(download_dir).ok()?
+            .flatten()
+            .filter_map(|entry| Srcinfo::from_dir(&entry.path()).ok())
+            .find(|info| info.package(package_name).is_some())
+            .map(|info| info.base.pkgbase)
+    }
+
+    pub fn clear_aur_path(config: &Config) -> Result<()> {
         let download_dir = &config.download_dir;
 
         if !download_dir.exists() {
@@ -614,194 +1382,538 @@ impl PackageManager {
             return Ok(());
         }
 
+        if config.dry_run {
+            let mut plan = Plan::default();
+            plan.extend(dirs_to_remove.into_iter().map(Step::RemoveDir));
+            plan.print();
+            return Ok(());
+        }
+
         Ui::warning(&format!("This will remove {} package folder(s) from {}",
             dirs_to_remove.len(), download_dir.display()));
         Ui::info("Removing package folders...");
 
         let mut removed_count = 0;
+        let mut events = Vec::new();
         for folder in &dirs_to_remove {
-            if let Err(e) = fs::remove_dir_all(folder) {
+            let version = Srcinfo::from_dir(folder).ok().map(|info| info.version());
+            let started = Instant::now();
+            let result = fs::remove_dir_all(folder);
+
+            let name = folder.file_name().unwrap_or_default().to_string_lossy();
+            events.push(Event::new(Action::Clear, &name, "aur")
+                .versions(version.as_deref(), None)
+                .took(started.elapsed())
+                .result(&result.as_ref().map_err(|e| anyhow::anyhow!("{}", e))));
+
+            if let Err(e) = result {
                 Ui::warning(&format!("Failed to remove {}: {}", folder.display(), e));
             } else {
                 removed_count += 1;
             }
         }
+        History::record(&events);
 
         Ui::success(&format!("Cleared AUR path: removed {} folder(s)", removed_count));
         Ok(())
     }
 
     /// List AUR packages tracked and installed via rauri.
-    pub fn list_installed() -> Result<()> {
-        let config = Config::load()?;
-        let download_dir = &config.download_dir;
-
-        if !download_dir.exists() {
-            Ui::info("No AUR packages found in download directory.");
-            return Ok(());
+    pub fn list_installed(config: &Config, renderer: &dyn Renderer) -> Result<()> {
+        let local_db = LocalDb::load()?;
+        let mut packages = Self::tracked_packages(config, &local_db);
+
+        if !packages.is_empty() {
+            let names: Vec<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+            let aur_info = Aur::get_packages_info(&names).unwrap_or_else(|e| {
+                Ui::warning(&format!("Could not check AUR for updates: {}", e));
+                HashMap::new()
+            });
+
+            for pkg in &mut packages {
+                pkg.latest_version = aur_info.get(&pkg.name).map(|aur_pkg| aur_pkg.version.clone());
+            }
         }
 
-        let mut packages: Vec<(String, String, String)> = Vec::new();
-
-        if let Ok(entries) = fs::read_dir(download_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.is_dir() {
-                    let repo_name = path.file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("")
-                        .to_string();
-
-                    if path.join("PKGBUILD").exists() {
-                        let mut pkg_name = repo_name.clone();
-                        let mut installed_packages: Vec<(String, String)> = Vec::new();
-
-                        if let Ok(pkg_files) = fs::read_dir(&path) {
-                            for pkg_file in pkg_files.flatten() {
-                                let pkg_path = pkg_file.path();
-                                if pkg_path.extension().and_then(|s| s.to_str()) == Some("zst") {
-                                    if let Some(file_name) = pkg_path.file_name().and_then(|n| n.to_str()) {
-                                        if file_name.ends_with(".pkg.tar.zst") && !file_name.ends_with("-debug.pkg.tar.zst") {
-                                            let result = Command::new("pacman")
-                                                .arg("-Qp")
-                                                .arg(&pkg_path)
-                                                .output();
-
-                                            if let Ok(output) = result {
-                                                if output.status.success() {
-                                                    let stdout = String::from_utf8_lossy(&output.stdout);
-                                                    let parts: Vec<&str> = stdout.trim().split_whitespace().collect();
-                                                    if parts.len() >= 2 {
-                                                        let file_pkg_name = parts[0];
-
-                                                        let check_result = Command::new("pacman")
-                                                            .arg("-Q")
-                                                            .arg(file_pkg_name)
-                                                            .output();
-
-                                                        if let Ok(check_output) = check_result {
-                                                            if check_output.status.success() {
-                                                                let installed_stdout = String::from_utf8_lossy(&check_output.stdout);
-                                                                let installed_parts: Vec<&str> = installed_stdout.trim().split_whitespace().collect();
-                                                                if installed_parts.len() >= 2 {
-                                                                    let installed_version = installed_parts[1];
-                                                                    installed_packages.push((file_pkg_name.to_string(), installed_version.to_string()));
-
-                                                                    if !file_pkg_name.ends_with("-debug") && pkg_name == repo_name {
-                                                                        pkg_name = file_pkg_name.to_string();
-                                                                    }
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
+        renderer.tracked(&packages);
+        Ok(())
+    }
 
-                        if let Some(main_pkg) = installed_packages.iter()
-                            .find(|p| !p.0.ends_with("-debug"))
-                            .or_else(|| installed_packages.first())
-                        {
-                            packages.push((repo_name, main_pkg.0.clone(), main_pkg.1.clone()));
-                        }
-                    }
+    /// The installed packages rauri tracks, without their AUR versions.
+    fn tracked_packages(config: &Config, local_db: &LocalDb) -> Vec<InstalledPackage> {
+        let mut packages: Vec<InstalledPackage> = Vec::new();
+
+        for (pkgbase, members) in PackageTracker::load_bases().unwrap_or_default() {
+            for pkgname in members {
+                if let Some(installed_version) = local_db.version(&pkgname) {
+                    packages.push(InstalledPackage {
+                        name: pkgname.clone(),
+                        pkgbase: pkgbase.clone(),
+                        version: installed_version.to_string(),
+                        source: "aur".to_string(),
+                        latest_version: None,
+                        repo_dir: render::repo_dir(&config.download_dir, &pkgbase),
+                    });
                 }
             }
         }
 
-        if packages.is_empty() {
-            Ui::info("No AUR packages found in download directory.");
-            return Ok(());
-        }
+        packages
+    }
 
-        let mut outdated = HashSet::new();
-        for (_, pkg_name, installed_version) in &packages {
-            if installed_version.contains("(not installed)") {
-                continue;
-            }
-            if let Ok(aur_pkg) = Aur::get_package_info(pkg_name) {
-                let clean_version = installed_version.replace(" (not installed)", "");
-                if clean_version != aur_pkg.version {
-                    outdated.insert(pkg_name.clone());
+    /// List all installed system packages (AUR + official) from the local pacman database.
+    pub fn list_all(config: &Config, renderer: &dyn Renderer) -> Result<()> {
+        let local_db = LocalDb::load()?;
+        let sync_db = SyncDb::load_or_warn();
+
+        renderer.all_installed(&Self::installed_packages(config, &local_db, &sync_db));
+        Ok(())
+    }
+
+    /// Every installed package with the repository that provides it.
+    pub fn installed_packages(config: &Config, local_db: &LocalDb, sync_db: &SyncDb) -> Vec<InstalledPackage> {
+        let tracked = PackageTracker::load().unwrap_or_default();
+
+        local_db.packages()
+            .map(|p| {
+                let pkgbase = p.base.clone().unwrap_or_else(|| p.name.clone());
+                let sync_pkg = sync_db.get(&p.name);
+                let source = match sync_pkg {
+                    Some(sync_pkg) => sync_pkg.repo.clone(),
+                    None if tracked.contains(&p.name) => "aur".to_string(),
+                    None => "local".to_string(),
+                };
+                let repo_dir = match sync_pkg {
+                    Some(_) => None,
+                    None => render::repo_dir(&config.download_dir, &pkgbase),
+                };
+                InstalledPackage {
+                    name: p.name.clone(),
+                    repo_dir,
+                    pkgbase,
+                    version: p.version.clone(),
+                    source,
+                    latest_version: sync_pkg.map(|s| s.version.clone()),
                 }
-            }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Filter;
+    use crate::paths;
+    use crate::rollback::BuildCache;
+    use crate::runner::{self, FakeRunner, RunnerGuard};
+    use std::rc::Rc;
+    use tempfile::TempDir;
+
+    const SRCINFO: &str = "pkgbase = foo\n\tpkgver = 1.1\n\tpkgrel = 1\n\npkgname = foo\n\npkgname = foo-docs\n";
+
+    /// A temporary home directory with a config that builds into `home/aur`, and a fake runner.
+    struct Sandbox {
+        fake: Rc<FakeRunner>,
+        config: Config,
+        home: TempDir,
+        _runner: RunnerGuard,
+        _home: paths::HomeGuard,
+    }
+
+    impl Sandbox {
+        fn new() -> Self {
+            let home = tempfile::tempdir().unwrap();
+            let home_guard = paths::install(home.path());
+
+            let config = Config {
+                download_dir: home.path().join("aur"),
+                review: false,
+                update_mirrors: false,
+                ..Config::default()
+            };
+            let fake = FakeRunner::new();
+            let runner = runner::install(fake.clone());
+            Sandbox { fake, config, home, _runner: runner, _home: home_guard }
         }
 
-        let is_tty = atty::is(Stream::Stdout);
-        for (repo_name, pkg_name, version) in &packages {
-            let is_outdated = outdated.contains(pkg_name);
-            let formatted = Ui::format_package(pkg_name, version, is_outdated);
-
-            if repo_name != pkg_name {
-                if is_tty {
-                    println!("  {}{}{} → {}", Colors::BOLD, repo_name.yellow(), Colors::RESET, formatted);
-                } else {
-                    println!("  {} → {}", repo_name, formatted);
-                }
-            } else {
-                println!("  {}", formatted);
+        /// A checkout of the split pkgbase foo (foo, foo-docs) in the download dir.
+        fn checkout(&self) -> PathBuf {
+            let dir = self.config.download_dir.join("foo");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(".SRCINFO"), SRCINFO).unwrap();
+            for archive in ["foo-1.1-1-x86_64.pkg.tar.zst", "foo-docs-1.1-1-x86_64.pkg.tar.zst"] {
+                fs::write(dir.join(archive), "").unwrap();
             }
+            self.fake.on("makepkg --packagelist", 0,
+                "foo-1.1-1-x86_64.pkg.tar.zst\nfoo-docs-1.1-1-x86_64.pkg.tar.zst\n");
+            dir
         }
 
-        if !outdated.is_empty() {
-            if is_tty {
-                println!("\n{}{}{}", Colors::BOLD,
-                    format!("{} packages have updates available", outdated.len()).yellow(),
-                    Colors::RESET);
-            } else {
-                println!("\n{} packages have updates available", outdated.len());
+        /// A local database with the given (name, version, pkgbase) packages installed.
+        fn local_db(&self, packages: &[(&str, &str, &str)]) -> LocalDb {
+            let db_path = self.home.path().join("pacman");
+            for (name, version, base) in packages {
+                let dir = db_path.join("local").join(format!("{}-{}", name, version));
+                fs::create_dir_all(&dir).unwrap();
+                fs::write(dir.join("desc"),
+                    format!("%NAME%\n{}\n\n%VERSION%\n{}\n\n%BASE%\n{}\n\n", name, version, base)).unwrap();
             }
+            LocalDb::load_from(&db_path).unwrap()
+        }
+    }
+
+    fn track(pkgbase: &str, pkgnames: &[&str]) {
+        let pkgnames: Vec<String> = pkgnames.iter().map(|n| n.to_string()).collect();
+        let build = tracker::Build { version: "1.0-1".to_string(), commit: None, reason: None };
+        PackageTracker::add_base(pkgbase, &pkgnames, &build).unwrap();
+    }
+
+    fn aur_package(name: &str, depends: &[&str]) -> AurPackage {
+        AurPackage {
+            name: name.to_string(),
+            package_base: name.to_string(),
+            version: "1.1-1".to_string(),
+            depends: depends.iter().map(|d| d.to_string()).collect(),
+            ..AurPackage::default()
         }
+    }
 
-        Ok(())
+    #[test]
+    fn installs_official_packages() {
+        let sandbox = Sandbox::new();
+        PackageManager::install_official(&["foo", "bar"]).unwrap();
+        assert_eq!(sandbox.fake.calls(), vec!["sudo pacman -S --noconfirm foo bar"]);
+
+        sandbox.fake.on("sudo pacman -S", 1, "");
+        assert!(PackageManager::install_official(&["baz"]).is_err());
     }
 
-    /// List all installed system packages (AUR + official) via pacman -Q.
-    pub fn list_all() -> Result<()> {
-        let is_tty = atty::is(Stream::Stdout);
+    #[test]
+    fn installs_packages_of_unreadable_repositories_with_pacman() {
+        let sandbox = Sandbox::new();
+        // Without readable sync databases pacman is asked instead.
+        sandbox.fake.on("pacman -Sddp --print-format %n glibc", 0, "glibc\n");
 
-        let output = Command::new("pacman")
-            .arg("-Q")
-            .output()
-            .context("Failed to run pacman -Q")?;
+        PackageManager::install(&["glibc"], false, &sandbox.config).unwrap();
+        assert!(sandbox.fake.calls().contains(&"sudo pacman -S --noconfirm glibc".to_string()));
+        assert!(PackageTracker::load_bases().unwrap().is_empty());
+    }
 
-        if !output.status.success() {
-            anyhow::bail!("Failed to list installed packages");
-        }
+    #[test]
+    fn builds_installs_and_tracks_aur_plan() {
+        let sandbox = Sandbox::new();
+        let dir = sandbox.checkout();
+        let plan = AurPlan {
+            selected: vec!["foo".to_string()],
+            builds: vec![AurBuild {
+                pkgbase: "foo".to_string(),
+                pkgs: vec![aur_package("foo", &["zlib"])],
+                repo_deps: vec!["zlib".to_string()],
+                is_target: true,
+                dir: dir.clone(),
+                cloned: true,
+            }],
+        };
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let packages: Vec<(&str, &str)> = stdout.lines()
-            .filter_map(|line| {
-                let mut parts = line.splitn(2, ' ');
-                let name = parts.next()?;
-                let version = parts.next()?;
-                Some((name, version))
-            })
-            .collect();
+        let mut summary = Summary::default();
+        PackageManager::execute_aur_plan(plan, &sandbox.config, &mut summary);
+        summary.finish("install").unwrap();
+
+        assert_eq!(sandbox.fake.calls(), vec![
+            "sudo pacman -S --needed --asdeps --noconfirm zlib".to_string(),
+            "makepkg -sf".to_string(),
+            "makepkg --packagelist".to_string(),
+            format!("sudo pacman -U {}", dir.join("foo-1.1-1-x86_64.pkg.tar.zst").display()),
+            "git rev-parse HEAD".to_string(),
+        ]);
+        let entry = PackageTracker::get("foo").unwrap();
+        assert_eq!(entry.pkgbase, "foo");
+        assert_eq!(entry.version.as_deref(), Some("1.1-1"));
+        assert_eq!(entry.commit, None);
+        assert_eq!(entry.reason, Some(InstallReason::Explicit));
+
+        let cached = BuildCache::builds("foo");
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].version, "1.1-1");
+        assert!(cached[0].path.starts_with(sandbox.home.path()));
+        assert!(BuildCache::builds("foo-docs").is_empty());
+    }
 
-        if packages.is_empty() {
-            Ui::info("No packages installed.");
-            return Ok(());
-        }
+    #[test]
+    fn installs_repo_deps_only_for_fetched_and_reviewed_builds() {
+        let sandbox = Sandbox::new();
+        let dir = sandbox.checkout();
+        sandbox.fake.on("git clone", 1, "");
+        let plan = AurPlan {
+            selected: vec!["bar".to_string(), "foo".to_string()],
+            builds: vec![
+                AurBuild {
+                    pkgbase: "bar".to_string(),
+                    pkgs: vec![aur_package("bar", &["zlib"])],
+                    repo_deps: vec!["zlib".to_string()],
+                    is_target: true,
+                    dir: sandbox.config.download_dir.join("bar"),
+                    cloned: false,
+                },
+                AurBuild {
+                    pkgbase: "foo".to_string(),
+                    pkgs: vec![aur_package("foo", &["glibc"])],
+                    repo_deps: vec!["glibc".to_string()],
+                    is_target: true,
+                    dir: dir.clone(),
+                    cloned: true,
+                },
+            ],
+        };
 
-        if is_tty {
-            println!("\n{}", format!("All Installed Packages ({}):", packages.len()).cyan().bold());
-        } else {
-            println!("\nAll Installed Packages ({}):", packages.len());
-        }
+        let mut summary = Summary::default();
+        PackageManager::execute_aur_plan(plan, &sandbox.config, &mut summary);
 
-        for (name, version) in &packages {
-            if is_tty {
-                println!("  {} {}", name.bold(), version.green());
-            } else {
-                println!("  {} {}", name, version);
-            }
+        let error = summary.finish("install").unwrap_err();
+        assert_eq!(error.to_string(), "Failed to install 1 of 2 packages");
+        let calls = sandbox.fake.calls();
+        assert!(calls[0].starts_with("git clone"));
+        assert_eq!(calls[1..3], ["sudo pacman -S --needed --asdeps --noconfirm glibc", "makepkg -sf"]);
+        assert!(PackageTracker::is_tracked("foo"));
+    }
+
+    #[test]
+    fn skips_targets_of_failed_dependencies() {
+        let sandbox = Sandbox::new();
+        sandbox.fake.on("makepkg -sf", 1, "");
+        let build = |name: &str, depends: &[&str], is_target: bool| AurBuild {
+            pkgbase: name.to_string(),
+            pkgs: vec![aur_package(name, depends)],
+            repo_deps: Vec::new(),
+            is_target,
+            dir: sandbox.config.download_dir.join(name),
+            cloned: true,
+        };
+        for name in ["bar", "foo"] {
+            let dir = sandbox.config.download_dir.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(".SRCINFO"),
+                format!("pkgbase = {0}\n\tpkgver = 1\n\tpkgrel = 1\n\npkgname = {0}\n", name)).unwrap();
         }
+        let plan = AurPlan {
+            selected: vec!["foo".to_string()],
+            builds: vec![build("bar", &[], false), build("foo", &["bar>=1"], true)],
+        };
 
-        Ok(())
+        let mut summary = Summary::default();
+        PackageManager::execute_aur_plan(plan, &sandbox.config, &mut summary);
+
+        let error = summary.finish("install").unwrap_err();
+        assert_eq!(error.to_string(), "dependency bar was not installed");
+        assert_eq!(sandbox.fake.calls(), vec!["makepkg -sf"]);
+        assert!(PackageTracker::load_bases().unwrap().is_empty());
+    }
+
+    #[test]
+    fn removes_packages_and_their_checkout() {
+        let sandbox = Sandbox::new();
+        let dir = sandbox.checkout();
+        let local_db = sandbox.local_db(&[("foo", "1.1-1", "foo"), ("foo-debug", "1.1-1", "foo")]);
+        track("foo", &["foo"]);
+
+        let mut summary = Summary::default();
+        let plan = PackageManager::plan_removal(&["foo"], &sandbox.config, &local_db, &mut summary);
+        PackageManager::execute_removal(plan, &mut summary);
+        summary.finish("remove").unwrap();
+
+        assert_eq!(sandbox.fake.calls(), vec![
+            "sudo pacman -R --noconfirm foo",
+            "sudo pacman -R --noconfirm foo-debug",
+        ]);
+        assert!(!dir.exists());
+        assert!(!PackageTracker::is_tracked("foo"));
+
+        let events = History::load(&Filter::default()).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].action, events[0].source.as_str()), (Action::Remove, "aur"));
+        assert_eq!(events[0].old_version.as_deref(), Some("1.1-1"));
+    }
+
+    #[test]
+    fn keeps_debug_package_tracked_when_its_removal_fails() {
+        let sandbox = Sandbox::new();
+        sandbox.checkout();
+        let local_db = sandbox.local_db(&[("foo", "1.1-1", "foo"), ("foo-debug", "1.1-1", "foo")]);
+        track("foo", &["foo", "foo-debug"]);
+        sandbox.fake.on("sudo pacman -R --noconfirm foo-debug", 1, "");
+
+        let mut summary = Summary::default();
+        let plan = PackageManager::plan_removal(&["foo"], &sandbox.config, &local_db, &mut summary);
+        PackageManager::execute_removal(plan, &mut summary);
+        summary.finish("remove").unwrap();
+
+        assert!(!PackageTracker::is_tracked("foo"));
+        assert!(PackageTracker::is_tracked("foo-debug"));
+    }
+
+    #[test]
+    fn keeps_checkout_when_removal_fails() {
+        let sandbox = Sandbox::new();
+        let dir = sandbox.checkout();
+        let local_db = sandbox.local_db(&[("foo", "1.1-1", "foo")]);
+        sandbox.fake.on("sudo pacman -R", 1, "");
+
+        let mut summary = Summary::default();
+        let plan = PackageManager::plan_removal(&["foo", "bar"], &sandbox.config, &local_db, &mut summary);
+        PackageManager::execute_removal(plan, &mut summary);
+
+        let error = summary.finish("remove").unwrap_err();
+        assert_eq!(error.to_string(), "Failed to remove 2 of 2 packages");
+        assert_eq!(sandbox.fake.calls(), vec!["sudo pacman -R --noconfirm foo"]);
+        assert!(dir.exists());
+
+        let failed = Filter { ok: Some(false), ..Filter::default() };
+        let events = History::load(&failed).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].source, "official");
+        assert_eq!(events[0].error.as_deref(), Some("Package removal failed"));
+    }
+
+    #[test]
+    fn updates_official_packages() {
+        let sandbox = Sandbox::new();
+        PackageManager::update_system(&sandbox.config, true, false).unwrap();
+        assert_eq!(sandbox.fake.calls(), vec!["sudo pacman -Syy", "sudo pacman -Syu --noconfirm"]);
+
+        PackageManager::update_system(&sandbox.config, true, true).unwrap();
+        assert_eq!(sandbox.fake.calls()[2..], ["sudo pacman -Syy", "sudo pacman -Syu --needed --noconfirm"]);
+
+        sandbox.fake.on("sudo pacman -Syy", 1, "");
+        assert!(PackageManager::update_system(&sandbox.config, true, false).is_err());
+        assert_eq!(sandbox.fake.calls()[4..], ["sudo pacman -Syy"]);
+    }
+
+    #[test]
+    fn updates_checkout_and_rebuilds_aur_package() {
+        let sandbox = Sandbox::new();
+        let dir = sandbox.checkout();
+        let url = Aur::clone_url("foo");
+        sandbox.fake.on("git rev-parse --show-toplevel", 0, &dir.display().to_string());
+        sandbox.fake.on("git remote get-url origin", 0, &url);
+        sandbox.fake.on("git rev-parse --verify --quiet origin/HEAD", 1, "");
+        track("foo", &["foo", "foo-docs"]);
+        track("old", &["old"]);
+
+        let update = AurUpdate {
+            stale: vec!["old".to_string()],
+            checked: 1,
+            rebuilds: vec![Rebuild {
+                pkgbase: "foo".to_string(),
+                pkgnames: vec!["foo".to_string(), "foo-docs".to_string()],
+                installed: vec!["1.0-1".to_string(), "1.0-1".to_string()],
+                reason: "Updating foo (foo, foo-docs) from 1.0-1 to 1.1-1...".to_string(),
+            }],
+            devel_store: DevelStore::default(),
+        };
+        PackageManager::execute_aur_update(update, &sandbox.config).unwrap();
+
+        assert_eq!(sandbox.fake.calls(), vec![
+            "git rev-parse --show-toplevel".to_string(),
+            "git rev-parse --verify HEAD".to_string(),
+            "git remote get-url origin".to_string(),
+            "git diff --name-only HEAD".to_string(),
+            "git fetch --quiet origin".to_string(),
+            "git rev-parse --verify --quiet origin/HEAD".to_string(),
+            "git reset --hard --quiet origin/master".to_string(),
+            "makepkg -sf".to_string(),
+            "makepkg --packagelist".to_string(),
+            format!("sudo pacman -U {} {}",
+                dir.join("foo-1.1-1-x86_64.pkg.tar.zst").display(),
+                dir.join("foo-docs-1.1-1-x86_64.pkg.tar.zst").display()),
+            "git rev-parse HEAD".to_string(),
+        ]);
+        assert!(!PackageTracker::is_tracked("old"));
+        let entry = PackageTracker::get("foo-docs").unwrap();
+        assert_eq!(entry.version.as_deref(), Some("1.1-1"));
+        assert!(entry.updated_at >= entry.installed_at);
+
+        let events = History::load(&Filter::default()).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[1].action, events[1].package.as_str()), (Action::Update, "foo-docs"));
+        assert_eq!(events[1].old_version.as_deref(), Some("1.0-1"));
+        assert_eq!(events[1].new_version.as_deref(), Some("1.1-1"));
+    }
+
+    #[test]
+    fn keeps_updating_after_a_failed_rebuild() {
+        let sandbox = Sandbox::new();
+        let dir = sandbox.checkout();
+        let url = Aur::clone_url("foo");
+        sandbox.fake.on("git clone", 1, "");
+        sandbox.fake.on("git rev-parse --show-toplevel", 0, &dir.display().to_string());
+        sandbox.fake.on("git remote get-url origin", 0, &url);
+        sandbox.fake.on("git rev-parse --verify --quiet origin/HEAD", 1, "");
+        track("foo", &["foo"]);
+
+        let rebuild = |pkgbase: &str| Rebuild {
+            pkgbase: pkgbase.to_string(),
+            pkgnames: vec![pkgbase.to_string()],
+            installed: vec!["1.0-1".to_string()],
+            reason: format!("Updating {}...", pkgbase),
+        };
+        let mut devel_store = DevelStore::default();
+        devel_store.record("bar-git", &[]).unwrap();
+        let update = AurUpdate {
+            stale: Vec::new(),
+            checked: 2,
+            rebuilds: vec![rebuild("bar-git"), rebuild("foo")],
+            devel_store,
+        };
+        let config = Config { devel: true, ..sandbox.config.clone() };
+
+        let error = PackageManager::execute_aur_update(update, &config).unwrap_err();
+        assert_eq!(error.to_string(), "Failed to update 1 of 2 packages");
+        assert!(sandbox.fake.calls().iter().any(|call| call.starts_with("sudo pacman -U")));
+        assert_eq!(PackageTracker::get("foo").unwrap().version.as_deref(), Some("1.1-1"));
+        assert!(DevelStore::load().unwrap().is_recorded("bar-git"));
+
+        let failed = Filter { ok: Some(false), ..Filter::default() };
+        assert_eq!(History::load(&failed).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn refuses_to_update_over_unreadable_devel_store() {
+        let sandbox = Sandbox::new();
+        let path = DevelStore::store_path();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "[packages\n").unwrap();
+
+        let config = Config { devel: true, ..sandbox.config.clone() };
+        let Err(error) = PackageManager::plan_aur_update(&config) else {
+            panic!("planned an update over an unreadable devel store");
+        };
+        assert_eq!(error.to_string(), "Failed to load devel package data");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[packages\n");
+    }
+
+    #[test]
+    fn lists_tracked_and_installed_packages() {
+        let sandbox = Sandbox::new();
+        let dir = sandbox.checkout();
+        let local_db = sandbox.local_db(&[
+            ("foo", "1.1-1", "foo"),
+            ("foo-docs", "1.1-1", "foo"),
+            ("glibc", "2.40-1", "glibc"),
+        ]);
+        track("foo", &["foo"]);
+
+        let tracked = PackageManager::tracked_packages(&sandbox.config, &local_db);
+        assert_eq!(tracked.len(), 1);
+        assert_eq!((tracked[0].name.as_str(), tracked[0].version.as_str()), ("foo", "1.1-1"));
+        assert_eq!(tracked[0].repo_dir.as_deref(), Some(dir.as_path()));
+
+        let installed = PackageManager::installed_packages(&sandbox.config, &local_db, &SyncDb::default());
+        let sources: Vec<(&str, &str)> = installed.iter()
+            .map(|p| (p.name.as_str(), p.source.as_str()))
+            .collect();
+        assert_eq!(sources, vec![("foo", "aur"), ("foo-docs", "local"), ("glibc", "local")]);
+        assert_eq!(installed[1].repo_dir.as_deref(), Some(dir.as_path()));
+        assert!(sandbox.fake.calls().is_empty());
     }
 }
@@ -0,0 +1,64 @@
+//! Where rauri keeps its files: the home directory `~` in paths, `~/.config/rauri` for
+//! configuration and state, and `~/.cache/rauri` (or `$XDG_CACHE_HOME/rauri`) for caches.
+//!
+//! Tests point all of them at a temporary directory for the current thread with
+//! `paths::install`, the way they replace the command runner, so that no test has to
+//! change `$HOME` for the whole process.
+
+use std::cell::RefCell;
+#[cfg(test)]
+use std::path::Path;
+use std::path::PathBuf;
+
+thread_local! {
+    static HOME: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+fn overridden() -> Option<PathBuf> {
+    HOME.with(|home| home.borrow().clone())
+}
+
+pub fn home_dir() -> PathBuf {
+    overridden().unwrap_or_else(|| dirs::home_dir().expect("Failed to get home directory"))
+}
+
+/// `~/.config/rauri`.
+pub fn config_dir() -> PathBuf {
+    home_dir().join(".config").join("rauri")
+}
+
+/// `~/.cache/rauri`.
+pub fn cache_dir() -> PathBuf {
+    let cache = match overridden() {
+        Some(home) => home.join(".cache"),
+        None => dirs::cache_dir().unwrap_or_else(|| home_dir().join(".cache")),
+    };
+    cache.join("rauri")
+}
+
+/// Expand a leading `~` in `path` to the home directory.
+pub fn expand_home(path: PathBuf) -> PathBuf {
+    match path.to_str().and_then(|p| p.strip_prefix('~')) {
+        Some(rest) => home_dir().join(rest.trim_start_matches('/')),
+        None => path,
+    }
+}
+
+/// Use `home` as this thread's home directory until the returned guard is dropped.
+#[cfg(test)]
+pub fn install(home: &Path) -> HomeGuard {
+    HomeGuard { previous: HOME.with(|current| current.replace(Some(home.to_path_buf()))) }
+}
+
+#[cfg(test)]
+pub struct HomeGuard {
+    previous: Option<PathBuf>,
+}
+
+#[cfg(test)]
+impl Drop for HomeGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        HOME.with(|current| current.replace(previous));
+    }
+}
@@ -0,0 +1,105 @@
+//! The side effects a command is about to have, collected before anything runs so
+//! `--dry-run` can print them instead of executing them.
+
+use atty::Stream;
+use colored::Colorize;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::ui::Ui;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// Clone the AUR repository at `url` into `dir`, or update the checkout there.
+    Clone { url: String, dir: PathBuf },
+    /// Show the PKGBUILD (or its changes) and ask before building, if review is enabled.
+    Review { dir: PathBuf },
+    /// Build the checkout in `dir` with makepkg and install `pkgnames` from it.
+    Build { dir: PathBuf, pkgnames: Vec<String>, as_dep: bool },
+    /// An external command, e.g. `sudo pacman -S ...`.
+    Run(Vec<String>),
+    RemoveDir(PathBuf),
+    Track { pkgbase: String, pkgnames: Vec<String> },
+    Untrack(String),
+    /// Remember the upstream commits a devel package was built from.
+    RecordDevel(String),
+    ForgetDevel(String),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Clone { url, dir } if dir.exists() => write!(f, "update checkout {} from {}", dir.display(), url),
+            Step::Clone { url, dir } => write!(f, "git clone {} {}", url, dir.display()),
+            Step::Review { dir } => write!(f, "review PKGBUILD in {}", dir.display()),
+            Step::Build { dir, pkgnames, as_dep } => {
+                let asdeps = if *as_dep { " --asdeps" } else { "" };
+                write!(f, "makepkg -sf in {}, then sudo pacman -U{} {}", dir.display(), asdeps, pkgnames.join(" "))
+            }
+            Step::Run(args) => write!(f, "{}", args.join(" ")),
+            Step::RemoveDir(dir) => write!(f, "delete {}", dir.display()),
+            Step::Track { pkgbase, pkgnames } => write!(f, "track {} ({})", pkgbase, pkgnames.join(", ")),
+            Step::Untrack(name) => write!(f, "untrack {}", name),
+            Step::RecordDevel(pkgbase) => write!(f, "record upstream commits of {}", pkgbase),
+            Step::ForgetDevel(pkgbase) => write!(f, "forget upstream commits of {}", pkgbase),
+        }
+    }
+}
+
+/// `sudo pacman <args> <packages>` as a step.
+pub fn pacman(args: &[&str], packages: &[&str]) -> Step {
+    Step::Run(["sudo", "pacman"].iter().chain(args).chain(packages).map(|s| s.to_string()).collect())
+}
+
+#[derive(Debug, Default)]
+pub struct Plan {
+    steps: Vec<Step>,
+}
+
+impl Plan {
+    pub fn push(&mut self, step: Step) {
+        self.steps.push(step);
+    }
+
+    pub fn extend(&mut self, steps: impl IntoIterator<Item = Step>) {
+        self.steps.extend(steps);
+    }
+
+    /// Print the numbered steps for --dry-run.
+    pub fn print(&self) {
+        if self.steps.is_empty() {
+            Ui::info("Dry run: nothing to do");
+            return;
+        }
+
+        Ui::info("Dry run: nothing was changed. Planned actions:");
+        let is_tty = atty::is(Stream::Stdout);
+        for (i, step) in self.steps.iter().enumerate() {
+            if is_tty {
+                println!("{} {}", format!("{:>3}.", i + 1).dimmed(), step);
+            } else {
+                println!("{:>3}. {}", i + 1, step);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_steps() {
+        assert_eq!(pacman(&["-S", "--noconfirm"], &["foo", "bar"]).to_string(),
+            "sudo pacman -S --noconfirm foo bar");
+        assert_eq!(Step::Build {
+            dir: PathBuf::from("/nonexistent/aur/foo"),
+            pkgnames: vec!["foo".to_string(), "foo-docs".to_string()],
+            as_dep: true,
+        }.to_string(), "makepkg -sf in /nonexistent/aur/foo, then sudo pacman -U --asdeps foo foo-docs");
+        assert_eq!(Step::Clone {
+            url: "https://aur.archlinux.org/foo.git".to_string(),
+            dir: PathBuf::from("/nonexistent/aur/foo"),
+        }.to_string(), "git clone https://aur.archlinux.org/foo.git /nonexistent/aur/foo");
+    }
+}
@@ -0,0 +1,430 @@
+//! Output of the commands that report on packages: search, list, list-all, info,
+//! check-updates and history.
+//!
+//! Commands collect what they found and hand it to a `Renderer`. `TextRenderer` prints
+//! the coloured, human-oriented listings; `JsonRenderer` (selected with `--json`) prints
+//! the stable JSON documented in the README. Messages from `Ui` are not part of this:
+//! with `--json` they are written to stderr as `{"level": ..., "message": ...}` objects.
+
+use atty::Stream;
+use colored::Colorize;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use crate::aur::AurPackage;
+use crate::history::Event;
+use crate::info::Card;
+use crate::localdb::LocalDb;
+use crate::syncdb::SyncPackage;
+use crate::ui::{Colors, Ui};
+use crate::vercmp::vercmp;
+
+/// Results of a search, each source sorted by relevance.
+pub struct SearchResults<'a> {
+    pub aur: &'a [AurPackage],
+    pub official: &'a [&'a SyncPackage],
+    /// Results shown per source; None shows all.
+    pub limit: Option<usize>,
+    pub local_db: Option<&'a LocalDb>,
+    /// Where AUR checkouts live, to report `repo_dir`.
+    pub download_dir: &'a Path,
+}
+
+/// An installed package as the list commands report it.
+pub struct InstalledPackage {
+    pub name: String,
+    pub pkgbase: String,
+    pub version: String,
+    /// "aur", the sync repository that has the package, or "local" if none has it.
+    pub source: String,
+    /// The newest version known to the AUR or the sync databases, if it was looked up.
+    pub latest_version: Option<String>,
+    /// The AUR checkout the package is built from, if there is one.
+    pub repo_dir: Option<PathBuf>,
+}
+
+impl InstalledPackage {
+    pub fn is_outdated(&self) -> bool {
+        self.latest_version.as_deref()
+            .is_some_and(|latest| vercmp(&self.version, latest) == Ordering::Less)
+    }
+}
+
+pub trait Renderer {
+    fn search(&self, results: &SearchResults);
+    /// AUR packages installed via rauri.
+    fn tracked(&self, packages: &[InstalledPackage]);
+    /// Every installed package.
+    fn all_installed(&self, packages: &[InstalledPackage]);
+    fn info(&self, cards: &[Card]);
+    /// Installed packages with a newer `latest_version`.
+    fn updates(&self, packages: &[InstalledPackage]);
+    /// Recorded installs, updates, removals and clears, oldest first.
+    fn history(&self, events: &[Event]);
+}
+
+/// The renderer for `--json` or for text output.
+pub fn renderer(json: bool) -> Box<dyn Renderer> {
+    if json { Box::new(JsonRenderer) } else { Box::new(TextRenderer) }
+}
+
+/// pacman -Ss style header: "extra/code 1.99.0-1 (group) [installed]".
+pub fn official_header(pkg: &SyncPackage, local_db: Option<&LocalDb>) -> String {
+    let mut header = format!("{}/{} {}", pkg.repo, pkg.name, pkg.version);
+    if !pkg.groups.is_empty() {
+        header.push_str(&format!(" ({})", pkg.groups.join(" ")));
+    }
+    match local_db.and_then(|db| db.version(&pkg.name)) {
+        Some(version) if version == pkg.version => header.push_str(" [installed]"),
+        Some(version) => header.push_str(&format!(" [installed: {}]", version)),
+        None => {}
+    }
+    header
+}
+
+/// The AUR checkout of `pkgbase` in `download_dir`, if it exists.
+pub fn repo_dir(download_dir: &Path, pkgbase: &str) -> Option<PathBuf> {
+    let dir = download_dir.join(pkgbase);
+    dir.is_dir().then_some(dir)
+}
+
+pub struct TextRenderer;
+
+impl TextRenderer {
+    fn heading(text: &str, is_tty: bool) {
+        if is_tty {
+            println!("\n{}", text.cyan().bold());
+        } else {
+            println!("\n{}", text);
+        }
+    }
+
+    fn more_results(hidden: usize, source: &str, is_tty: bool) {
+        if is_tty {
+            println!("  {}... and {} more {} results. Use --search-all to see all{}",
+                Colors::DIM, hidden, source, Colors::RESET);
+        } else {
+            println!("  ... and {} more {} results. Use --search-all to see all", hidden, source);
+        }
+    }
+
+    fn format_package(name: &str, version: &str, outdated: bool, is_tty: bool) -> String {
+        if !is_tty {
+            if outdated {
+                return format!("{} {} (outdated)", name, version);
+            } else {
+                return format!("{} {}", name, version);
+            }
+        }
+
+        if outdated {
+            format!("{} {} {}", name.bold(), version.yellow(), "(outdated)".yellow())
+        } else {
+            format!("{} {}", name.bold(), version.green())
+        }
+    }
+}
+
+impl Renderer for TextRenderer {
+    fn search(&self, results: &SearchResults) {
+        let is_tty = atty::is(Stream::Stdout);
+        let shown = |total: usize| results.limit.map_or(total, |n| n.min(total));
+
+        if !results.aur.is_empty() {
+            Self::heading("AUR Packages:", is_tty);
+
+            for pkg in results.aur.iter().take(shown(results.aur.len())) {
+                let desc = pkg.description.as_ref()
+                    .map(|d| format!(" - {}", d))
+                    .unwrap_or_default();
+                let stats = format!("+{} {:.2}", pkg.votes, pkg.popularity);
+                let flag = if pkg.out_of_date.is_some() { " (out of date)" } else { "" };
+                if is_tty {
+                    println!("  {}{}{} {}({}) {}{}{}{}",
+                        Colors::BOLD, pkg.name.yellow(), Colors::RESET,
+                        Colors::DIM, pkg.version, stats, Colors::RESET, flag.red(), desc);
+                } else {
+                    println!("  {} ({}) {}{}{}", pkg.name, pkg.version, stats, flag, desc);
+                }
+            }
+
+            if results.aur.len() > shown(results.aur.len()) {
+                Self::more_results(results.aur.len() - shown(results.aur.len()), "AUR", is_tty);
+            }
+        }
+
+        if !results.official.is_empty() {
+            Self::heading("Official Repository Packages:", is_tty);
+
+            for pkg in results.official.iter().take(shown(results.official.len())) {
+                let header = official_header(pkg, results.local_db);
+                if is_tty {
+                    println!("{}", header.bold());
+                } else {
+                    println!("{}", header);
+                }
+                if let Some(desc) = &pkg.description {
+                    println!("    {}", desc);
+                }
+            }
+
+            if results.official.len() > shown(results.official.len()) {
+                Self::more_results(results.official.len() - shown(results.official.len()), "official", is_tty);
+            }
+        }
+
+        if results.aur.is_empty() && results.official.is_empty() {
+            Ui::warning("No packages found");
+        }
+    }
+
+    fn tracked(&self, packages: &[InstalledPackage]) {
+        if packages.is_empty() {
+            Ui::info("No AUR packages installed via rauri.");
+            return;
+        }
+
+        let is_tty = atty::is(Stream::Stdout);
+        for pkg in packages {
+            let formatted = Self::format_package(&pkg.name, &pkg.version, pkg.is_outdated(), is_tty);
+
+            if pkg.pkgbase != pkg.name {
+                if is_tty {
+                    println!("  {}{}{} → {}", Colors::BOLD, pkg.pkgbase.yellow(), Colors::RESET, formatted);
+                } else {
+                    println!("  {} → {}", pkg.pkgbase, formatted);
+                }
+            } else {
+                println!("  {}", formatted);
+            }
+        }
+
+        let outdated = packages.iter().filter(|p| p.is_outdated()).count();
+        if outdated > 0 {
+            if is_tty {
+                println!("\n{}{}{}", Colors::BOLD,
+                    format!("{} packages have updates available", outdated).yellow(),
+                    Colors::RESET);
+            } else {
+                println!("\n{} packages have updates available", outdated);
+            }
+        }
+    }
+
+    fn all_installed(&self, packages: &[InstalledPackage]) {
+        if packages.is_empty() {
+            Ui::info("No packages installed.");
+            return;
+        }
+
+        let is_tty = atty::is(Stream::Stdout);
+        Self::heading(&format!("All Installed Packages ({}):", packages.len()), is_tty);
+
+        for pkg in packages {
+            if is_tty {
+                println!("  {} {}", pkg.name.bold(), pkg.version.green());
+            } else {
+                println!("  {} {}", pkg.name, pkg.version);
+            }
+        }
+    }
+
+    fn info(&self, cards: &[Card]) {
+        let is_tty = atty::is(Stream::Stdout);
+        for card in cards {
+            println!("{}\n", card.render(is_tty));
+        }
+    }
+
+    /// "name old -> new" lines like checkupdates, so scripts can count them.
+    fn updates(&self, packages: &[InstalledPackage]) {
+        let is_tty = atty::is(Stream::Stdout);
+        if packages.is_empty() {
+            if is_tty {
+                Ui::info("No updates available");
+            }
+            return;
+        }
+
+        for pkg in packages {
+            let latest = pkg.latest_version.as_deref().unwrap_or_default();
+            if is_tty {
+                println!("{} {} -> {} {}", pkg.name.bold(), pkg.version.red(), latest.green(),
+                    format!("({})", pkg.source).dimmed());
+            } else {
+                println!("{} {} -> {}", pkg.name, pkg.version, latest);
+            }
+        }
+    }
+
+    /// "2024-05-01 13:37 UTC update foo 1.0-1 -> 1.1-1 (aur, 12.3s)", with the error
+    /// on the next line for failures.
+    fn history(&self, events: &[Event]) {
+        let is_tty = atty::is(Stream::Stdout);
+        if events.is_empty() {
+            if is_tty {
+                Ui::info("No matching history");
+            }
+            return;
+        }
+
+        for event in events {
+            let versions = match (&event.old_version, &event.new_version) {
+                (Some(old), Some(new)) => format!(" {} -> {}", old, new),
+                (Some(version), None) | (None, Some(version)) => format!(" {}", version),
+                (None, None) => String::new(),
+            };
+            let action = format!("{:?}", event.action).to_lowercase();
+            let details = format!("({}, {:.1}s)", event.source, event.duration_ms as f64 / 1000.0);
+            let date = Ui::format_date(event.time);
+
+            if is_tty {
+                let action = if event.ok { action.green() } else { format!("{} failed", action).red() };
+                println!("{} {} {}{} {}", date.dimmed(), action, event.package.bold(), versions,
+                    details.dimmed());
+            } else {
+                let action = if event.ok { action } else { format!("{} failed", action) };
+                println!("{} {} {}{} {}", date, action, event.package, versions, details);
+            }
+            if let Some(error) = &event.error {
+                println!("    {}", error);
+            }
+        }
+    }
+}
+
+/// One package in the JSON output of search and the list commands.
+#[derive(Debug, Serialize)]
+struct PackageEntry<'a> {
+    name: &'a str,
+    pkgbase: Option<&'a str>,
+    /// The available version for search results, the installed one for listings.
+    version: &'a str,
+    source: &'a str,
+    description: Option<&'a str>,
+    installed_version: Option<&'a str>,
+    latest_version: Option<&'a str>,
+    /// Installed and older than `latest_version`.
+    outdated: bool,
+    repo_dir: Option<&'a Path>,
+}
+
+impl<'a> PackageEntry<'a> {
+    fn from_installed(pkg: &'a InstalledPackage) -> Self {
+        PackageEntry {
+            name: &pkg.name,
+            pkgbase: Some(&pkg.pkgbase),
+            version: &pkg.version,
+            source: &pkg.source,
+            description: None,
+            installed_version: Some(&pkg.version),
+            latest_version: pkg.latest_version.as_deref(),
+            outdated: pkg.is_outdated(),
+            repo_dir: pkg.repo_dir.as_deref(),
+        }
+    }
+
+    fn from_search(
+        name: &'a str,
+        pkgbase: Option<&'a str>,
+        version: &'a str,
+        source: &'a str,
+        description: Option<&'a str>,
+        local_db: Option<&'a LocalDb>,
+    ) -> Self {
+        let installed_version = local_db.and_then(|db| db.version(name));
+        PackageEntry {
+            name,
+            pkgbase,
+            version,
+            source,
+            description,
+            installed_version,
+            latest_version: Some(version),
+            outdated: installed_version.is_some_and(|v| vercmp(v, version) == Ordering::Less),
+            repo_dir: None,
+        }
+    }
+}
+
+pub struct JsonRenderer;
+
+impl JsonRenderer {
+    fn print<T: Serialize + ?Sized>(value: &T) {
+        match serde_json::to_string_pretty(value) {
+            Ok(json) => println!("{}", json),
+            Err(e) => Ui::error(&format!("Failed to serialize output: {}", e)),
+        }
+    }
+}
+
+impl Renderer for JsonRenderer {
+    fn search(&self, results: &SearchResults) {
+        let limit = results.limit.unwrap_or(usize::MAX);
+        let repo_dirs: Vec<Option<PathBuf>> = results.aur.iter().take(limit)
+            .map(|pkg| repo_dir(results.download_dir, &pkg.package_base))
+            .collect();
+
+        let aur = results.aur.iter().take(limit).zip(&repo_dirs).map(|(pkg, dir)| PackageEntry {
+            repo_dir: dir.as_deref(),
+            ..PackageEntry::from_search(&pkg.name, Some(&pkg.package_base), &pkg.version, "aur",
+                pkg.description.as_deref(), results.local_db)
+        });
+        let official = results.official.iter().take(limit).map(|pkg| PackageEntry::from_search(
+            &pkg.name, pkg.base.as_deref(), &pkg.version, &pkg.repo,
+            pkg.description.as_deref(), results.local_db));
+
+        Self::print(&aur.chain(official).collect::<Vec<_>>());
+    }
+
+    fn tracked(&self, packages: &[InstalledPackage]) {
+        Self::print(&packages.iter().map(PackageEntry::from_installed).collect::<Vec<_>>());
+    }
+
+    fn all_installed(&self, packages: &[InstalledPackage]) {
+        Self::print(&packages.iter().map(PackageEntry::from_installed).collect::<Vec<_>>());
+    }
+
+    fn info(&self, cards: &[Card]) {
+        Self::print(&cards.iter().map(Card::to_json).collect::<Vec<_>>());
+    }
+
+    fn updates(&self, packages: &[InstalledPackage]) {
+        Self::print(&packages.iter().map(PackageEntry::from_installed).collect::<Vec<_>>());
+    }
+
+    fn history(&self, events: &[Event]) {
+        Self::print(events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_installed_packages() {
+        let pkg = InstalledPackage {
+            name: "foo-docs".to_string(),
+            pkgbase: "foo".to_string(),
+            version: "1.0-1".to_string(),
+            source: "aur".to_string(),
+            latest_version: Some("1.0-2".to_string()),
+            repo_dir: Some(PathBuf::from("/tmp/aur/foo")),
+        };
+
+        let json = serde_json::to_value(PackageEntry::from_installed(&pkg)).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "name": "foo-docs",
+            "pkgbase": "foo",
+            "version": "1.0-1",
+            "source": "aur",
+            "description": null,
+            "installed_version": "1.0-1",
+            "latest_version": "1.0-2",
+            "outdated": true,
+            "repo_dir": "/tmp/aur/foo",
+        }));
+    }
+}
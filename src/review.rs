@@ -0,0 +1,229 @@
+use anyhow::{Context, Result};
+use atty::Stream;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::aur::git_output;
+use crate::config::Config;
+use crate::runner::Cmd;
+use crate::tracker::PackageTracker;
+use crate::ui::Ui;
+
+pub struct Review;
+
+/// What the user has to look at before a pkgbase is built.
+#[derive(Debug, PartialEq)]
+enum Pending {
+    /// Reviewed at this commit already, or only .SRCINFO changed since.
+    Nothing,
+    /// Never reviewed: the PKGBUILD and .install files.
+    Files(Vec<PathBuf>),
+    /// Changed since the review of commit `from`.
+    Diff { from: String, diff: String },
+}
+
+/// The PKGBUILD plus any .install scripts, which run as root during installation.
+fn build_files(package_dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![package_dir.join("PKGBUILD")];
+
+    if let Ok(entries) = fs::read_dir(package_dir) {
+        let mut install_files: Vec<PathBuf> = entries.flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("install"))
+            .collect();
+        install_files.sort();
+        files.extend(install_files);
+    }
+
+    files
+}
+
+impl Review {
+    /// Let the user inspect what is about to be built before makepkg runs it.
+    ///
+    /// The first time a pkgbase is built this shows its PKGBUILD and .install files;
+    /// afterwards only the diff since the last reviewed commit. Files are opened in
+    /// `config.editor` if set, otherwise in the pager. Fails if the user aborts, and
+    /// without a terminal when there is something to review: unattended builds need
+    /// `review = false`.
+    pub fn review(package_dir: &Path, pkgbase: &str, config: &Config) -> Result<()> {
+        if !config.review {
+            return Ok(());
+        }
+
+        let head = git_output(package_dir, &["rev-parse", "HEAD"])?;
+        let last_reviewed = PackageTracker::reviewed_commit(pkgbase);
+        // Colour only helps in the pager; editors get a plain diff.
+        let color = if config.editor.is_some() { "--color=never" } else { "--color=always" };
+        let pending = Self::pending(package_dir, &head, last_reviewed.as_deref(), color)?;
+
+        if pending == Pending::Nothing {
+            if last_reviewed.as_deref() != Some(head.as_str()) {
+                Self::mark_reviewed(pkgbase, &head);
+            }
+            return Ok(());
+        }
+
+        if !atty::is(Stream::Stdin) || !atty::is(Stream::Stdout) {
+            anyhow::bail!("{} needs a review, which requires a terminal. Set review = false in {} to build without one",
+                pkgbase, Config::config_path().display());
+        }
+
+        match pending {
+            Pending::Nothing => {}
+            Pending::Diff { from, diff } => {
+                Ui::info(&format!("Changes to {} since last review ({}..{}):",
+                    pkgbase, &from[..from.len().min(8)], &head[..head.len().min(8)]));
+                Self::show_text(&diff, pkgbase, config)?;
+            }
+            Pending::Files(files) => {
+                Ui::info(&format!("Reviewing build files of {}", pkgbase));
+                Self::show_files(&files, config)?;
+            }
+        }
+
+        let answer = Ui::prompt(&format!("Proceed with building {}? [Y/n]", pkgbase))?;
+        if !(answer.is_empty() || answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")) {
+            anyhow::bail!("Aborted building {}", pkgbase);
+        }
+
+        Self::mark_reviewed(pkgbase, &head);
+        Ok(())
+    }
+
+    /// What changed in `package_dir` at `head` since the `last_reviewed` commit. A
+    /// commit the checkout doesn't have (e.g. after the AUR history was rewritten)
+    /// counts as never reviewed.
+    fn pending(package_dir: &Path, head: &str, last_reviewed: Option<&str>, color: &str) -> Result<Pending> {
+        let last_reviewed = last_reviewed
+            .filter(|c| git_output(package_dir, &["cat-file", "-e", &format!("{}^{{commit}}", c)]).is_ok());
+
+        match last_reviewed {
+            Some(commit) if commit == head => Ok(Pending::Nothing),
+            Some(commit) => {
+                let diff = git_output(package_dir, &[
+                    "diff", color, commit, head, "--", ".", ":(exclude).SRCINFO",
+                ])?;
+                if diff.is_empty() {
+                    Ok(Pending::Nothing)
+                } else {
+                    Ok(Pending::Diff { from: commit.to_string(), diff })
+                }
+            }
+            None => Ok(Pending::Files(build_files(package_dir))),
+        }
+    }
+
+    fn mark_reviewed(pkgbase: &str, commit: &str) {
+        if let Err(e) = PackageTracker::set_reviewed(pkgbase, commit) {
+            Ui::warning(&format!("Failed to record review of {}: {}", pkgbase, e));
+        }
+    }
+
+    fn show_files(files: &[PathBuf], config: &Config) -> Result<()> {
+        if let Some(editor) = &config.editor {
+            return Self::open_in_editor(editor, files);
+        }
+
+        let mut text = String::new();
+        for file in files {
+            let content = fs::read_to_string(file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let name = file.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            text.push_str(&format!("==> {} <==\n{}\n", name, content));
+        }
+
+        Self::page(&text)
+    }
+
+    fn show_text(text: &str, pkgbase: &str, config: &Config) -> Result<()> {
+        match &config.editor {
+            Some(editor) => {
+                // A fresh file only we can open, removed when `file` is dropped.
+                let mut file = tempfile::Builder::new()
+                    .prefix(&format!("rauri-{}-", pkgbase))
+                    .suffix(".diff")
+                    .tempfile()
+                    .context("Failed to create a temporary file for the diff")?;
+                file.write_all(text.as_bytes())
+                    .with_context(|| format!("Failed to write {}", file.path().display()))?;
+                Self::open_in_editor(editor, &[file.path().to_path_buf()])
+            }
+            None => Self::page(text),
+        }
+    }
+
+    fn open_in_editor(editor: &str, files: &[PathBuf]) -> Result<()> {
+        let mut parts = editor.split_whitespace();
+        let program = parts.next().context("Configured editor is empty")?;
+
+        let status = Cmd::new(program)
+            .args(parts)
+            .args(files)
+            .status()
+            .with_context(|| format!("Failed to run editor '{}'", editor))?;
+
+        if !status.success() {
+            anyhow::bail!("Editor '{}' exited with an error", editor);
+        }
+
+        Ok(())
+    }
+
+    /// Show `text` in $PAGER, falling back to `less -R`.
+    fn page(text: &str) -> Result<()> {
+        let pager = match std::env::var("PAGER") {
+            Ok(pager) if !pager.trim().is_empty() => Cmd::new("sh").arg("-c").arg(pager),
+            _ => Cmd::new("less").arg("-R"),
+        };
+        pager.status_with_input(text).context("Failed to run pager")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, FakeRunner};
+
+    #[test]
+    fn finds_what_needs_review() {
+        let tmp = tempfile::tempdir().unwrap();
+        for file in ["PKGBUILD", "foo.install", ".SRCINFO"] {
+            fs::write(tmp.path().join(file), "").unwrap();
+        }
+        let fake = FakeRunner::new();
+        fake.on("git cat-file -e gone^{commit}", 1, "");
+        fake.on("git diff --color=never old new", 0, "-pkgver=1\n+pkgver=2\n");
+        fake.on("git diff --color=never same new", 0, "");
+        let _runner = runner::install(fake.clone());
+
+        let files = Pending::Files(vec![tmp.path().join("PKGBUILD"), tmp.path().join("foo.install")]);
+        assert_eq!(Review::pending(tmp.path(), "new", None, "--color=never").unwrap(), files);
+        assert_eq!(Review::pending(tmp.path(), "new", Some("gone"), "--color=never").unwrap(), files);
+        assert_eq!(Review::pending(tmp.path(), "new", Some("new"), "--color=never").unwrap(), Pending::Nothing);
+        assert_eq!(Review::pending(tmp.path(), "new", Some("old"), "--color=never").unwrap(), Pending::Diff {
+            from: "old".to_string(),
+            diff: "-pkgver=1\n+pkgver=2".to_string(),
+        });
+        // Only .SRCINFO changed.
+        assert_eq!(Review::pending(tmp.path(), "new", Some("same"), "--color=never").unwrap(), Pending::Nothing);
+    }
+
+    #[test]
+    fn opens_diff_in_private_temporary_file() {
+        let fake = FakeRunner::new();
+        let _runner = runner::install(fake.clone());
+        let config = Config { editor: Some("vim -R".to_string()), ..Config::default() };
+
+        Review::show_text("+pkgver=2\n", "foo", &config).unwrap();
+
+        let calls = fake.calls();
+        let path = calls[0].strip_prefix("vim -R ").unwrap();
+        assert!(path.contains("rauri-foo-") && path.ends_with(".diff"));
+        assert_ne!(Path::new(path), std::env::temp_dir().join("rauri-foo.diff"));
+        assert!(!Path::new(path).exists());
+    }
+}
@@ -0,0 +1,214 @@
+//! `rauri rollback`: reinstall an earlier build of an AUR package.
+//!
+//! Updating a package resets its checkout, so the archives makepkg built before are
+//! gone after the next build. Once a build is installed its archives are copied to
+//! `~/.cache/rauri/builds/<pkgname>/`, which keeps the newest `keep_builds` versions of
+//! each package; `rauri rollback` reinstalls one of them with `pacman -U`.
+
+use anyhow::{Context, Result};
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::aur::pkgname_from_file;
+use crate::config::Config;
+use crate::history::{self, Action, Event, History};
+use crate::localdb::LocalDb;
+use crate::paths;
+use crate::plan::{self, Plan};
+use crate::runner::Cmd;
+use crate::tracker::PackageTracker;
+use crate::ui::Ui;
+use crate::vercmp::vercmp;
+
+/// A cached package archive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedBuild {
+    pub version: String,
+    pub path: PathBuf,
+}
+
+/// Version from an archive name like `foo-docs-1:1.2-3-any.pkg.tar.zst`.
+fn version_from_file(file_name: &str) -> Option<&str> {
+    let stem = &file_name[..file_name.find(".pkg.tar")?];
+    let pkgname = pkgname_from_file(file_name)?;
+    let arch_start = stem.rfind('-')?;
+    stem.get(pkgname.len() + 1..arch_start)
+}
+
+pub struct BuildCache;
+
+impl BuildCache {
+    pub fn cache_dir() -> PathBuf {
+        paths::cache_dir().join("builds")
+    }
+
+    /// Copy freshly installed archives into the cache, keeping the newest `keep` builds
+    /// of each package. Failing to do so is only a warning.
+    pub fn store(files: &[PathBuf], keep: usize) {
+        if keep == 0 {
+            return;
+        }
+        let cache_dir = Self::cache_dir();
+        for file in files {
+            if let Err(e) = Self::store_in(&cache_dir, file, keep) {
+                Ui::warning(&format!("Failed to cache {}: {:#}", file.display(), e));
+            }
+        }
+    }
+
+    fn store_in(cache_dir: &Path, file: &Path, keep: usize) -> Result<()> {
+        let file_name = file.file_name().and_then(|n| n.to_str())
+            .with_context(|| format!("Not a package archive: {}", file.display()))?;
+        let pkgname = pkgname_from_file(file_name)
+            .with_context(|| format!("Not a package archive: {}", file.display()))?;
+
+        let dir = cache_dir.join(pkgname);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create cache directory: {}", dir.display()))?;
+        fs::copy(file, dir.join(file_name))?;
+
+        for old in Self::builds_in(&dir).into_iter().skip(keep) {
+            fs::remove_file(&old.path)
+                .with_context(|| format!("Failed to remove {}", old.path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// The cached builds of `package_name`, newest first.
+    pub fn builds(package_name: &str) -> Vec<CachedBuild> {
+        Self::builds_in(&Self::cache_dir().join(package_name))
+    }
+
+    fn builds_in(dir: &Path) -> Vec<CachedBuild> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let mut builds: Vec<CachedBuild> = entries.flatten()
+            .filter_map(|entry| {
+                let version = version_from_file(entry.file_name().to_str()?)?.to_string();
+                Some(CachedBuild { version, path: entry.path() })
+            })
+            .collect();
+        builds.sort_by(|a, b| vercmp(&b.version, &a.version));
+        builds
+    }
+}
+
+pub struct Rollback;
+
+impl Rollback {
+    /// The build to install: `requested` if given, else the newest one older than
+    /// `installed`.
+    fn pick<'a>(builds: &'a [CachedBuild], installed: Option<&str>, requested: Option<&str>) -> Result<&'a CachedBuild> {
+        let cached = || builds.iter().map(|b| b.version.as_str()).collect::<Vec<_>>().join(", ");
+
+        match (requested, installed) {
+            (Some(version), _) => builds.iter().find(|b| b.version == version)
+                .ok_or_else(|| anyhow::anyhow!("{} is not cached (cached: {})", version, cached())),
+            (None, Some(installed)) => builds.iter()
+                .find(|b| vercmp(&b.version, installed) == Ordering::Less)
+                .ok_or_else(|| anyhow::anyhow!("No build older than {} is cached (cached: {})", installed, cached())),
+            (None, None) => anyhow::bail!("not installed, name the version to install (cached: {})", cached()),
+        }
+    }
+
+    /// Reinstall a cached build of `package_name`: `version`, or the one before the
+    /// installed version.
+    pub fn run(package_name: &str, version: Option<&str>, config: &Config) -> Result<()> {
+        let builds = BuildCache::builds(package_name);
+        if builds.is_empty() {
+            anyhow::bail!("No cached builds of {} in {} (keep_builds = {})",
+                package_name, BuildCache::cache_dir().display(), config.keep_builds);
+        }
+
+        let local_db = LocalDb::load()?;
+        let installed = local_db.version(package_name);
+        let build = Self::pick(&builds, installed, version)
+            .with_context(|| format!("Cannot roll back {}", package_name))?;
+
+        if installed == Some(build.version.as_str()) {
+            Ui::info(&format!("{} {} is already installed", package_name, build.version));
+            return Ok(());
+        }
+
+        let path = build.path.to_string_lossy();
+        if config.dry_run {
+            let mut plan = Plan::default();
+            plan.push(plan::pacman(&["-U"], &[&path]));
+            plan.print();
+            return Ok(());
+        }
+
+        Ui::info(&format!("Rolling back {} from {} to {}...",
+            package_name, installed.unwrap_or("(not installed)"), build.version));
+        let started = Instant::now();
+        let result = Cmd::new("sudo")
+            .arg("pacman")
+            .arg("-U")
+            .arg(&build.path)
+            .status()
+            .context("Failed to execute pacman -U")
+            .and_then(|status| match status.success() {
+                true => Ok(()),
+                false => Err(anyhow::anyhow!("Failed to install {}", build.path.display())),
+            });
+
+        History::record(&[Event::new(Action::Rollback, package_name, "aur")
+            .versions(installed, Some(&build.version))
+            .took(started.elapsed())
+            .result(&result)]);
+        result?;
+
+        Ui::success(&format!("Rolled back {} to {}", package_name, build.version));
+        if let Some(mut entry) = PackageTracker::get(package_name) {
+            // The commit the build came from is not recorded with the archive.
+            entry.version = Some(build.version.clone());
+            entry.commit = None;
+            entry.updated_at = Some(history::now());
+            let pinned = entry.pinned;
+            if let Err(e) = PackageTracker::insert([(package_name.to_string(), entry)]) {
+                Ui::warning(&format!("Failed to track package: {}", e));
+            }
+            if !pinned {
+                Ui::info(&format!("The next update reinstalls the AUR version; set pinned = true for {} in {} to keep this one",
+                    package_name, PackageTracker::tracking_file_path().display()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caches_builds_and_picks_rollback_target() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache_dir = tmp.path().join("builds");
+        for version in ["1.0-1", "1:0.9-1", "1.2-1", "1.1-1"] {
+            let file = tmp.path().join(format!("foo-docs-{}-any.pkg.tar.zst", version));
+            fs::write(&file, version).unwrap();
+            BuildCache::store_in(&cache_dir, &file, 3).unwrap();
+        }
+
+        // 1:0.9-1 has an epoch, so it is the newest; 1.0-1 was pruned.
+        let builds = BuildCache::builds_in(&cache_dir.join("foo-docs"));
+        let versions: Vec<&str> = builds.iter().map(|b| b.version.as_str()).collect();
+        assert_eq!(versions, vec!["1:0.9-1", "1.2-1", "1.1-1"]);
+        assert_eq!(fs::read_to_string(&builds[2].path).unwrap(), "1.1-1");
+
+        assert_eq!(Rollback::pick(&builds, Some("1.2-1"), None).unwrap().version, "1.1-1");
+        assert_eq!(Rollback::pick(&builds, Some("1.2-1"), Some("1:0.9-1")).unwrap().version, "1:0.9-1");
+        let error = Rollback::pick(&builds, Some("1.1-1"), None).unwrap_err();
+        assert_eq!(error.to_string(), "No build older than 1.1-1 is cached (cached: 1:0.9-1, 1.2-1, 1.1-1)");
+        assert!(Rollback::pick(&builds, None, None).is_err());
+        assert!(Rollback::pick(&builds, None, Some("1.0-1")).is_err());
+
+        assert_eq!(version_from_file("foo-docs-1:1.2-3-x86_64.pkg.tar.zst"), Some("1:1.2-3"));
+        assert_eq!(version_from_file("PKGBUILD"), None);
+    }
+}
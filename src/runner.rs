@@ -0,0 +1,220 @@
+//! Every external program rauri runs (pacman, sudo, git, makepkg, reflector, ...) is
+//! described as a `Cmd` and executed by the thread's `CommandRunner`. Normally that is
+//! `SystemRunner`; tests install a `FakeRunner` that records the command lines and
+//! answers them from a script, so the package flows can run without touching the system.
+
+use std::cell::RefCell;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Write};
+#[cfg(test)]
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::rc::Rc;
+
+/// A command to run, like `std::process::Command` but inspectable.
+#[derive(Debug, Clone)]
+pub struct Cmd {
+    program: OsString,
+    args: Vec<OsString>,
+    dir: Option<PathBuf>,
+    envs: Vec<(OsString, OsString)>,
+}
+
+impl Cmd {
+    pub fn new(program: impl AsRef<OsStr>) -> Self {
+        Cmd { program: program.as_ref().to_owned(), args: Vec::new(), dir: None, envs: Vec::new() }
+    }
+
+    pub fn arg(mut self, arg: impl AsRef<OsStr>) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args.extend(args.into_iter().map(|a| a.as_ref().to_owned()));
+        self
+    }
+
+    pub fn current_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn env(mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
+        self.envs.push((key.as_ref().to_owned(), value.as_ref().to_owned()));
+        self
+    }
+
+    /// The program and its arguments separated by spaces, e.g. "sudo pacman -R foo".
+    #[cfg(test)]
+    pub fn line(&self) -> String {
+        std::iter::once(&self.program)
+            .chain(&self.args)
+            .map(|s| s.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Run with the terminal attached and wait for it to exit.
+    pub fn status(&self) -> io::Result<ExitStatus> {
+        current().status(self)
+    }
+
+    /// Run with stdout and stderr captured.
+    pub fn output(&self) -> io::Result<Output> {
+        current().output(self)
+    }
+
+    /// Run with `input` written to stdin, e.g. a pager.
+    pub fn status_with_input(&self, input: &str) -> io::Result<ExitStatus> {
+        current().status_with_input(self, input)
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        if let Some(dir) = &self.dir {
+            command.current_dir(dir);
+        }
+        for (key, value) in &self.envs {
+            command.env(key, value);
+        }
+        command
+    }
+}
+
+pub trait CommandRunner {
+    fn status(&self, cmd: &Cmd) -> io::Result<ExitStatus>;
+    fn output(&self, cmd: &Cmd) -> io::Result<Output>;
+    fn status_with_input(&self, cmd: &Cmd, input: &str) -> io::Result<ExitStatus>;
+}
+
+/// Runs commands for real.
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn status(&self, cmd: &Cmd) -> io::Result<ExitStatus> {
+        cmd.command().status()
+    }
+
+    fn output(&self, cmd: &Cmd) -> io::Result<Output> {
+        cmd.command().output()
+    }
+
+    fn status_with_input(&self, cmd: &Cmd, input: &str) -> io::Result<ExitStatus> {
+        let mut child = cmd.command().stdin(Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // The program may exit before reading everything, like a pager the user quits.
+            stdin.write_all(input.as_bytes()).ok();
+        }
+        child.wait()
+    }
+}
+
+thread_local! {
+    static RUNNER: RefCell<Rc<dyn CommandRunner>> = RefCell::new(Rc::new(SystemRunner));
+}
+
+fn current() -> Rc<dyn CommandRunner> {
+    RUNNER.with(|runner| runner.borrow().clone())
+}
+
+/// Run this thread's commands with `runner` until the returned guard is dropped.
+#[cfg(test)]
+pub fn install(runner: Rc<dyn CommandRunner>) -> RunnerGuard {
+    RunnerGuard { previous: RUNNER.with(|current| current.replace(runner)) }
+}
+
+#[cfg(test)]
+pub struct RunnerGuard {
+    previous: Rc<dyn CommandRunner>,
+}
+
+#[cfg(test)]
+impl Drop for RunnerGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.clone();
+        RUNNER.with(|current| current.replace(previous));
+    }
+}
+
+/// Records every command line and answers from a script. Commands without a matching
+/// rule succeed with no output.
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeRunner {
+    calls: RefCell<Vec<String>>,
+    /// (command line prefix, exit code, stdout), first match wins.
+    script: RefCell<Vec<(String, i32, String)>>,
+}
+
+#[cfg(test)]
+impl FakeRunner {
+    pub fn new() -> Rc<Self> {
+        Rc::new(FakeRunner::default())
+    }
+
+    /// Answer commands whose line starts with `prefix` with exit `code` and `stdout`.
+    pub fn on(&self, prefix: &str, code: i32, stdout: &str) {
+        self.script.borrow_mut().push((prefix.to_string(), code, stdout.to_string()));
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.borrow().clone()
+    }
+
+    fn answer(&self, cmd: &Cmd) -> Output {
+        let line = cmd.line();
+        self.calls.borrow_mut().push(line.clone());
+
+        let script = self.script.borrow();
+        let (code, stdout) = script.iter()
+            .find(|(prefix, _, _)| line.starts_with(prefix.as_str()))
+            .map_or((0, ""), |(_, code, stdout)| (*code, stdout.as_str()));
+
+        Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: if code == 0 { Vec::new() } else { b"scripted failure".to_vec() },
+        }
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for FakeRunner {
+    fn status(&self, cmd: &Cmd) -> io::Result<ExitStatus> {
+        Ok(self.answer(cmd).status)
+    }
+
+    fn output(&self, cmd: &Cmd) -> io::Result<Output> {
+        Ok(self.answer(cmd))
+    }
+
+    fn status_with_input(&self, cmd: &Cmd, _input: &str) -> io::Result<ExitStatus> {
+        Ok(self.answer(cmd).status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fake_runner_records_and_answers() {
+        let fake = FakeRunner::new();
+        fake.on("pacman -T", 127, "foo\n");
+        let _guard = install(fake.clone());
+
+        let output = Cmd::new("pacman").arg("-T").arg("foo").output().unwrap();
+        assert_eq!(output.status.code(), Some(127));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "foo\n");
+        assert!(Cmd::new("sudo").args(["pacman", "-Syy"]).status().unwrap().success());
+
+        assert_eq!(fake.calls(), vec!["pacman -T foo", "sudo pacman -Syy"]);
+    }
+}
@@ -0,0 +1,267 @@
+//! Parser for the `.SRCINFO` metadata shipped with every AUR package.
+//!
+//! A .SRCINFO has one `pkgbase` section followed by one section per `pkgname`.
+//! Package sections inherit everything from the pkgbase section and may override
+//! individual keys; an empty value (`depends =`) clears the inherited array.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Values of an array key for one architecture. `arch` is None for the plain key
+/// (`depends`) and e.g. Some("x86_64") for `depends_x86_64`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArchVec {
+    pub arch: Option<String>,
+    pub values: Vec<String>,
+}
+
+/// Fields that only exist on the pkgbase.
+#[derive(Debug, Clone, Default)]
+pub struct PackageBase {
+    pub pkgbase: String,
+    pub pkgver: String,
+    pub pkgrel: String,
+    pub epoch: Option<String>,
+    pub source: Vec<ArchVec>,
+}
+
+/// A single package built from the pkgbase, with pkgbase values already inherited.
+#[derive(Debug, Clone, Default)]
+pub struct Package {
+    pub pkgname: String,
+    pub depends: Vec<ArchVec>,
+    pub optdepends: Vec<ArchVec>,
+    pub provides: Vec<ArchVec>,
+    pub conflicts: Vec<ArchVec>,
+    pub replaces: Vec<ArchVec>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Srcinfo {
+    pub base: PackageBase,
+    pub packages: Vec<Package>,
+}
+
+/// Arch-specific keys may be overridden by package sections.
+const PACKAGE_ARRAYS: &[&str] = &["depends", "optdepends", "provides", "conflicts", "replaces"];
+
+/// One `key[_arch] = value` line.
+#[derive(Debug, Clone)]
+struct Entry {
+    key: String,
+    arch: Option<String>,
+    value: String,
+}
+
+/// Split `depends_x86_64` into ("depends", Some("x86_64")). Only keys that can be
+/// arch-specific are split, so `pkgbase` or `install` stay intact.
+fn split_key(key: &str) -> (String, Option<String>) {
+    const ARCH_KEYS: &[&str] = &[
+        "source", "makedepends", "checkdepends", "depends", "optdepends",
+        "provides", "conflicts", "replaces", "md5sums", "sha1sums", "sha224sums",
+        "sha256sums", "sha384sums", "sha512sums", "b2sums", "cksums",
+    ];
+
+    for base in ARCH_KEYS {
+        if let Some(arch) = key.strip_prefix(base).and_then(|r| r.strip_prefix('_')) {
+            return (base.to_string(), Some(arch.to_string()));
+        }
+    }
+
+    (key.to_string(), None)
+}
+
+/// Group all values of `key` by architecture, in order of first appearance.
+fn arch_vecs(entries: &[Entry], key: &str) -> Vec<ArchVec> {
+    let mut vecs: Vec<ArchVec> = Vec::new();
+
+    for entry in entries.iter().filter(|e| e.key == key) {
+        let idx = match vecs.iter().position(|v| v.arch == entry.arch) {
+            Some(i) => i,
+            None => {
+                vecs.push(ArchVec { arch: entry.arch.clone(), values: Vec::new() });
+                vecs.len() - 1
+            }
+        };
+        if !entry.value.is_empty() {
+            vecs[idx].values.push(entry.value.clone());
+        }
+    }
+
+    vecs
+}
+
+fn value(entries: &[Entry], key: &str) -> Option<String> {
+    entries.iter()
+        .find(|e| e.key == key && e.arch.is_none())
+        .map(|e| e.value.clone())
+}
+
+/// Values of a package section override the pkgbase per (key, arch) pair.
+fn overridden(base: &[ArchVec], pkg: &[ArchVec]) -> Vec<ArchVec> {
+    let mut merged: Vec<ArchVec> = base.iter()
+        .filter(|b| !pkg.iter().any(|p| p.arch == b.arch))
+        .cloned()
+        .collect();
+    merged.extend(pkg.iter().cloned());
+    merged.retain(|v| !v.values.is_empty());
+    merged
+}
+
+/// All values applicable to `arch`: the plain key plus the `_arch` variant.
+pub fn for_arch<'a>(vecs: &'a [ArchVec], arch: &'a str) -> impl Iterator<Item = &'a str> {
+    vecs.iter()
+        .filter(move |v| v.arch.as_deref().is_none_or(|a| a == arch))
+        .flat_map(|v| v.values.iter().map(|s| s.as_str()))
+}
+
+impl Srcinfo {
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut base_entries: Vec<Entry> = Vec::new();
+        let mut pkg_sections: Vec<(String, Vec<Entry>)> = Vec::new();
+
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line.split_once('=')
+                .with_context(|| format!(".SRCINFO line {}: expected 'key = value'", lineno + 1))?;
+            let (key, value) = (key.trim(), value.trim());
+
+            match key {
+                "pkgbase" => {
+                    if !base_entries.is_empty() || !pkg_sections.is_empty() {
+                        anyhow::bail!(".SRCINFO line {}: pkgbase must come first", lineno + 1);
+                    }
+                    base_entries.push(Entry { key: key.to_string(), arch: None, value: value.to_string() });
+                }
+                "pkgname" => pkg_sections.push((value.to_string(), Vec::new())),
+                _ => {
+                    let (key, arch) = split_key(key);
+                    let entry = Entry { key, arch, value: value.to_string() };
+                    match pkg_sections.last_mut() {
+                        Some((_, entries)) => entries.push(entry),
+                        None if !base_entries.is_empty() => base_entries.push(entry),
+                        None => anyhow::bail!(".SRCINFO line {}: missing pkgbase", lineno + 1),
+                    }
+                }
+            }
+        }
+
+        let pkgbase = value(&base_entries, "pkgbase").context(".SRCINFO has no pkgbase")?;
+        if pkg_sections.is_empty() {
+            anyhow::bail!(".SRCINFO for {} has no pkgname", pkgbase);
+        }
+
+        let base = PackageBase {
+            pkgbase,
+            pkgver: value(&base_entries, "pkgver").unwrap_or_default(),
+            pkgrel: value(&base_entries, "pkgrel").unwrap_or_default(),
+            epoch: value(&base_entries, "epoch").filter(|e| !e.is_empty()),
+            source: arch_vecs(&base_entries, "source"),
+        };
+
+        let packages = pkg_sections.into_iter().map(|(pkgname, entries)| {
+            let mut pkg = Package { pkgname, ..Default::default() };
+
+            for key in PACKAGE_ARRAYS {
+                let merged = overridden(&arch_vecs(&base_entries, key), &arch_vecs(&entries, key));
+                match *key {
+                    "depends" => pkg.depends = merged,
+                    "optdepends" => pkg.optdepends = merged,
+                    "provides" => pkg.provides = merged,
+                    "conflicts" => pkg.conflicts = merged,
+                    _ => pkg.replaces = merged,
+                }
+            }
+
+            pkg
+        }).collect();
+
+        Ok(Srcinfo { base, packages })
+    }
+
+    /// Parse the .SRCINFO of a cloned AUR repository.
+    pub fn from_dir(package_dir: &Path) -> Result<Self> {
+        let path = package_dir.join(".SRCINFO");
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Full version as pacman reports it: `[epoch:]pkgver-pkgrel`.
+    pub fn version(&self) -> String {
+        match &self.base.epoch {
+            Some(epoch) => format!("{}:{}-{}", epoch, self.base.pkgver, self.base.pkgrel),
+            None => format!("{}-{}", self.base.pkgver, self.base.pkgrel),
+        }
+    }
+
+    pub fn pkgnames(&self) -> impl Iterator<Item = &str> {
+        self.packages.iter().map(|p| p.pkgname.as_str())
+    }
+
+    pub fn package(&self, pkgname: &str) -> Option<&Package> {
+        self.packages.iter().find(|p| p.pkgname == pkgname)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPLIT: &str = "\
+pkgbase = foo
+\tpkgdesc = Foo base
+\tpkgver = 1.2.3
+\tpkgrel = 2
+\tepoch = 1
+\tarch = x86_64
+\tlicense = MIT
+\tmakedepends = cmake
+\tdepends = glibc
+\tdepends_x86_64 = lib32-glibc
+\tsource = git+https://example.com/foo.git
+\tsource_aarch64 = extra.patch
+
+pkgname = foo
+\tdepends = glibc
+\tdepends = zlib
+
+pkgname = foo-docs
+\tpkgdesc = Foo documentation
+\tarch = any
+\tdepends =
+\tdepends_x86_64 =
+";
+
+    #[test]
+    fn parses_split_package() {
+        let info = Srcinfo::parse(SPLIT).unwrap();
+
+        assert_eq!(info.base.pkgbase, "foo");
+        assert_eq!(info.version(), "1:1.2.3-2");
+        assert_eq!(info.pkgnames().collect::<Vec<_>>(), vec!["foo", "foo-docs"]);
+        assert_eq!(for_arch(&info.base.source, "x86_64").count(), 1);
+        assert_eq!(for_arch(&info.base.source, "aarch64").count(), 2);
+
+        let foo = info.package("foo").unwrap();
+        assert_eq!(
+            for_arch(&foo.depends, "x86_64").collect::<Vec<_>>(),
+            vec!["lib32-glibc", "glibc", "zlib"]
+        );
+
+        let docs = info.package("foo-docs").unwrap();
+        assert!(docs.depends.is_empty());
+    }
+
+    #[test]
+    fn rejects_missing_pkgbase() {
+        assert!(Srcinfo::parse("pkgname = foo\n").is_err());
+        assert!(Srcinfo::parse("pkgbase = foo\n\tpkgver = 1\n").is_err());
+    }
+}
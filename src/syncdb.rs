@@ -0,0 +1,433 @@
+//! Reader for pacman's sync databases, the package lists of the official repositories.
+//!
+//! `<dbpath>/sync/<repo>.db` is a (usually gzip compressed) tar archive with one
+//! directory per package. Each directory holds a `desc` file in the same `%KEY%`
+//! format as the local database; databases written by older pacman versions keep
+//! dependencies in a separate `depends` file.
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use regex::RegexBuilder;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::{symlink, MetadataExt};
+use std::path::{Path, PathBuf};
+
+use crate::deps::dep_name;
+use crate::localdb::{DescFields, DEFAULT_DB_PATH};
+use crate::runner::Cmd;
+use crate::ui::Ui;
+
+const PACMAN_CONF: &str = "/etc/pacman.conf";
+
+#[derive(Debug, Clone)]
+pub struct SyncPackage {
+    pub repo: String,
+    pub name: String,
+    pub base: Option<String>,
+    pub version: String,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub license: Vec<String>,
+    pub groups: Vec<String>,
+    /// Installed size in bytes.
+    pub installed_size: Option<u64>,
+    pub packager: Option<String>,
+    /// Unix timestamp.
+    pub build_date: Option<i64>,
+    pub depends: Vec<String>,
+    pub optdepends: Vec<String>,
+    pub provides: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub replaces: Vec<String>,
+}
+
+impl SyncPackage {
+    fn parse(repo: &str, content: &str) -> Result<Self> {
+        let desc = DescFields::parse(content);
+
+        Ok(SyncPackage {
+            repo: repo.to_string(),
+            name: desc.first("NAME").context("desc has no %NAME%")?,
+            base: desc.first("BASE"),
+            version: desc.first("VERSION").context("desc has no %VERSION%")?,
+            description: desc.first("DESC"),
+            url: desc.first("URL"),
+            license: desc.all("LICENSE"),
+            groups: desc.all("GROUPS"),
+            installed_size: desc.first("ISIZE").and_then(|s| s.parse().ok()),
+            packager: desc.first("PACKAGER"),
+            build_date: desc.first("BUILDDATE").and_then(|d| d.parse().ok()),
+            depends: desc.all("DEPENDS"),
+            optdepends: desc.all("OPTDEPENDS"),
+            provides: desc.all("PROVIDES"),
+            conflicts: desc.all("CONFLICTS"),
+            replaces: desc.all("REPLACES"),
+        })
+    }
+}
+
+/// Repositories enabled in pacman.conf, in priority order.
+pub fn configured_repos(pacman_conf: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(pacman_conf)
+        .with_context(|| format!("Failed to read {}", pacman_conf.display()))?;
+
+    Ok(content.lines()
+        .filter_map(|line| line.trim().strip_prefix('[')?.strip_suffix(']'))
+        .map(|section| section.trim())
+        .filter(|section| *section != "options")
+        .map(|section| section.to_string())
+        .collect())
+}
+
+/// Read every package of one repository database file.
+fn read_repo(path: &Path, repo: &str) -> Result<Vec<SyncPackage>> {
+    let data = fs::read(path)
+        .with_context(|| format!("Failed to read sync database: {}", path.display()))?;
+
+    let reader: Box<dyn Read + '_> = match data.get(..4) {
+        Some([0x1f, 0x8b, _, _]) => Box::new(GzDecoder::new(data.as_slice())),
+        Some([0x28, 0xb5, 0x2f, 0xfd]) => {
+            anyhow::bail!("{} is zstd compressed, which is not supported", path.display())
+        }
+        _ => Box::new(data.as_slice()),
+    };
+
+    // Concatenate all files of a package directory; their %KEY% sections don't overlap.
+    let mut order: Vec<String> = Vec::new();
+    let mut contents: HashMap<String, String> = HashMap::new();
+
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries().with_context(|| format!("Failed to read {}", path.display()))? {
+        let mut entry = entry.with_context(|| format!("Failed to read {}", path.display()))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let entry_path = entry.path()?.to_string_lossy().to_string();
+        let dir = match entry_path.split_once('/') {
+            Some((dir, _)) => dir.to_string(),
+            None => continue,
+        };
+
+        let mut content = String::new();
+        entry.read_to_string(&mut content)
+            .with_context(|| format!("Failed to read {} in {}", entry_path, path.display()))?;
+
+        match contents.get_mut(&dir) {
+            Some(existing) => {
+                existing.push('\n');
+                existing.push_str(&content);
+            }
+            None => {
+                order.push(dir.clone());
+                contents.insert(dir, content);
+            }
+        }
+    }
+
+    order.iter()
+        .map(|dir| SyncPackage::parse(repo, &contents[dir])
+            .with_context(|| format!("Failed to parse {} in {}", dir, path.display())))
+        .collect()
+}
+
+/// DBPath of the private database copy used to check for updates, like checkupdates'
+/// `$CHECKUPDATES_DB`. It is kept between runs so pacman only downloads what changed.
+fn check_db_path() -> PathBuf {
+    let uid = fs::metadata("/proc/self").map(|m| m.uid()).unwrap_or(0);
+    std::env::temp_dir().join(format!("rauri-checkup-db-{}", uid))
+}
+
+/// Copy the system's sync databases into `sync_dir` where they are newer than the copies.
+fn copy_newer_databases(system_sync: &Path, sync_dir: &Path) -> Result<()> {
+    let entries = fs::read_dir(system_sync)
+        .with_context(|| format!("Failed to read {}", system_sync.display()))?;
+
+    for entry in entries.flatten() {
+        let source = entry.path();
+        if source.extension().is_none_or(|ext| ext != "db") {
+            continue;
+        }
+
+        let target = sync_dir.join(entry.file_name());
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified(&target) < modified(&source) {
+            fs::copy(&source, &target)
+                .with_context(|| format!("Failed to copy {}", source.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// All packages of the configured repositories.
+#[derive(Debug, Default)]
+pub struct SyncDb {
+    /// In repository priority order, so the first match of a name wins like in pacman.
+    packages: Vec<SyncPackage>,
+    by_name: HashMap<String, usize>,
+    /// Some repositories could not be read, so a missing package may still exist.
+    incomplete: bool,
+}
+
+impl SyncDb {
+    /// Read the repositories enabled in /etc/pacman.conf from pacman's default DBPath.
+    pub fn load() -> Result<Self> {
+        let repos = configured_repos(Path::new(PACMAN_CONF))?;
+        Self::load_from(Path::new(DEFAULT_DB_PATH), &repos)
+    }
+
+    /// Refresh a private copy of the sync databases and read it, the way `checkupdates`
+    /// does: no root is needed and pacman's own databases stay as they are, so checking
+    /// never sets up a partial upgrade. Needs fakeroot.
+    pub fn load_refreshed() -> Result<Self> {
+        let db_path = check_db_path();
+        let sync_dir = db_path.join("sync");
+        fs::create_dir_all(&sync_dir)
+            .with_context(|| format!("Failed to create {}", sync_dir.display()))?;
+
+        let system_db = Path::new(DEFAULT_DB_PATH);
+        let local = db_path.join("local");
+        if fs::symlink_metadata(&local).is_err() {
+            symlink(system_db.join("local"), &local)
+                .with_context(|| format!("Failed to link {}", local.display()))?;
+        }
+        copy_newer_databases(&system_db.join("sync"), &sync_dir)?;
+
+        let output = Cmd::new("fakeroot")
+            .args(["--", "pacman", "-Sy", "--dbpath"])
+            .arg(&db_path)
+            .args(["--logfile", "/dev/null"])
+            .output();
+        let output = match output {
+            Ok(output) => output,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                anyhow::bail!("fakeroot is needed to check for updates without root (pacman -S fakeroot)")
+            }
+            Err(e) => return Err(e).context("Failed to execute pacman -Sy"),
+        };
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("Failed to sync the temporary databases: {}", stderr.trim());
+        }
+
+        let repos = configured_repos(Path::new(PACMAN_CONF))?;
+        Self::load_from(&db_path, &repos)
+    }
+
+    /// `load`, or no packages after a warning if the databases can't be read, so that
+    /// AUR packages can still be handled.
+    pub fn load_or_warn() -> Self {
+        Self::load().unwrap_or_else(|e| {
+            Ui::warning(&format!("Failed to read sync databases: {:#}", e));
+            SyncDb { incomplete: true, ..SyncDb::default() }
+        })
+    }
+
+    /// Read `<db_path>/sync/<repo>.db` for each of `repos`, highest priority first.
+    /// Repositories that have never been synced are skipped, and so are databases that
+    /// can't be read (e.g. zstd compressed ones), with a warning.
+    pub fn load_from(db_path: &Path, repos: &[String]) -> Result<Self> {
+        let mut db = SyncDb::default();
+
+        for repo in repos {
+            let path = db_path.join("sync").join(format!("{}.db", repo));
+            if !path.exists() {
+                continue;
+            }
+
+            let packages = match read_repo(&path, repo) {
+                Ok(packages) => packages,
+                Err(e) => {
+                    Ui::warning(&format!("Skipping the {} repository: {:#}", repo, e));
+                    db.incomplete = true;
+                    continue;
+                }
+            };
+            for package in packages {
+                db.by_name.entry(package.name.clone()).or_insert(db.packages.len());
+                db.packages.push(package);
+            }
+        }
+
+        Ok(db)
+    }
+
+    /// The package called `name` from the highest priority repository that has it.
+    pub fn get(&self, name: &str) -> Option<&SyncPackage> {
+        self.by_name.get(name).map(|&i| &self.packages[i])
+    }
+
+    pub fn packages(&self) -> impl Iterator<Item = &SyncPackage> {
+        self.packages.iter()
+    }
+
+    /// Packages that provide `name`, not counting packages that are called `name`.
+    pub fn providers(&self, name: &str) -> Vec<&SyncPackage> {
+        self.packages.iter()
+            .filter(|p| p.provides.iter().any(|provide| dep_name(provide) == name))
+            .collect()
+    }
+
+    /// The package pacman would pick for the dependency `dep`: a package of that name,
+    /// otherwise the first provider. Version constraints are not checked.
+    pub fn satisfier(&self, dep: &str) -> Option<&SyncPackage> {
+        let name = dep_name(dep);
+        self.get(name).or_else(|| self.providers(name).into_iter().next())
+    }
+
+    /// The name of the package satisfying `dep` in a repository rauri could not read,
+    /// asked from pacman, which reads every database format. None if all were read.
+    pub fn unread_satisfier(&self, dep: &str) -> Option<String> {
+        if !self.incomplete {
+            return None;
+        }
+        let output = Cmd::new("pacman")
+            .args(["-Sddp", "--print-format", "%n", dep])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        String::from_utf8_lossy(&output.stdout).lines()
+            .map(|line| line.trim().to_string())
+            .find(|line| !line.is_empty())
+    }
+
+    /// Packages whose name, description or provides match `query`, which is a
+    /// case-insensitive regular expression as with `pacman -Ss`.
+    pub fn search(&self, query: &str) -> Vec<&SyncPackage> {
+        let regex = RegexBuilder::new(query)
+            .case_insensitive(true)
+            .build()
+            .or_else(|_| RegexBuilder::new(&regex::escape(query)).case_insensitive(true).build())
+            .expect("escaped pattern is a valid regex");
+
+        self.packages.iter()
+            .filter(|p| regex.is_match(&p.name)
+                || p.description.as_deref().is_some_and(|d| regex.is_match(d))
+                || p.provides.iter().any(|provide| regex.is_match(dep_name(provide))))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, FakeRunner};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    /// Write a gzip compressed repository database containing `packages`, given as
+    /// (directory, file name, content).
+    fn write_db(path: &Path, files: &[(&str, &str, &str)]) {
+        let file = fs::File::create(path).unwrap();
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+        for (dir, name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, format!("{}/{}", dir, name), content.as_bytes()).unwrap();
+        }
+
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    fn fixture() -> (tempfile::TempDir, SyncDb) {
+        let tmp = tempfile::tempdir().unwrap();
+        let sync = tmp.path().join("sync");
+        fs::create_dir_all(&sync).unwrap();
+
+        write_db(&sync.join("core.db"), &[
+            ("bash-5.2-1", "desc", "%NAME%\nbash\n\n%VERSION%\n5.2-1\n\n\
+                %DESC%\nThe GNU Bourne Again shell\n\n%PROVIDES%\nsh\n\n"),
+            ("zlib-1:1.3-1", "desc", "%NAME%\nzlib\n\n%VERSION%\n1:1.3-1\n\n%DESC%\nCompression library\n\n"),
+        ]);
+        write_db(&sync.join("extra.db"), &[
+            ("zlib-1:1.4-1", "desc", "%NAME%\nzlib\n\n%VERSION%\n1:1.4-1\n\n"),
+            ("python-3.12-1", "desc", "%NAME%\npython\n\n%VERSION%\n3.12-1\n\n%ISIZE%\n1024\n\n"),
+            // Old database format with dependencies in their own file.
+            ("python-3.12-1", "depends", "%DEPENDS%\nzlib\nbash\n\n"),
+        ]);
+        // Unreadable databases are skipped instead of failing every lookup.
+        fs::write(sync.join("multilib.db"), [0x28, 0xb5, 0x2f, 0xfd, 0, 0]).unwrap();
+
+        let repos = vec!["core".to_string(), "extra".to_string(), "multilib".to_string()];
+        let db = SyncDb::load_from(tmp.path(), &repos).unwrap();
+        (tmp, db)
+    }
+
+    #[test]
+    fn looks_up_packages_in_priority_order() {
+        let (_tmp, db) = fixture();
+
+        let zlib = db.get("zlib").unwrap();
+        assert_eq!(zlib.repo, "core");
+        assert_eq!(zlib.version, "1:1.3-1");
+
+        let python = db.get("python").unwrap();
+        assert_eq!(python.repo, "extra");
+        assert_eq!(python.installed_size, Some(1024));
+        assert_eq!(python.depends, vec!["zlib", "bash"]);
+
+        assert!(db.get("sh").is_none());
+        assert_eq!(db.satisfier("sh>=5").map(|p| p.name.as_str()), Some("bash"));
+        assert!(db.satisfier("fish").is_none());
+    }
+
+    #[test]
+    fn searches_names_descriptions_and_provides() {
+        let (_tmp, db) = fixture();
+
+        let names = |query: &str| db.search(query).iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names("BOURNE"), vec!["bash"]);
+        assert_eq!(names("^sh$"), vec!["bash"]);
+        assert_eq!(names("^py"), vec!["python"]);
+        assert_eq!(names("lib("), Vec::<String>::new());
+    }
+
+    #[test]
+    fn asks_pacman_about_unread_repositories() {
+        let (_tmp, db) = fixture();
+        let fake = FakeRunner::new();
+        fake.on("pacman -Sddp --print-format %n lib32-glibc", 0, "lib32-glibc\n");
+        fake.on("pacman -Sddp", 1, "");
+        let _runner = runner::install(fake.clone());
+
+        assert_eq!(db.unread_satisfier("lib32-glibc").as_deref(), Some("lib32-glibc"));
+        assert_eq!(db.unread_satisfier("foo"), None);
+        assert_eq!(SyncDb::default().unread_satisfier("lib32-glibc"), None);
+        assert_eq!(fake.calls().len(), 2);
+    }
+
+    #[test]
+    fn copies_only_newer_databases() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (system, copy) = (tmp.path().join("system"), tmp.path().join("copy"));
+        fs::create_dir_all(&system).unwrap();
+        fs::create_dir_all(&copy).unwrap();
+
+        fs::write(system.join("core.db"), "old").unwrap();
+        fs::write(system.join("core.db.sig"), "sig").unwrap();
+        copy_newer_databases(&system, &copy).unwrap();
+        assert_eq!(fs::read_to_string(copy.join("core.db")).unwrap(), "old");
+        assert!(!copy.join("core.db.sig").exists());
+
+        // A copy refreshed by pacman -Sy is newer than the system's and must stay.
+        fs::write(copy.join("core.db"), "synced").unwrap();
+        copy_newer_databases(&system, &copy).unwrap();
+        assert_eq!(fs::read_to_string(copy.join("core.db")).unwrap(), "synced");
+    }
+
+    #[test]
+    fn reads_repositories_from_pacman_conf() {
+        let tmp = tempfile::tempdir().unwrap();
+        let conf = tmp.path().join("pacman.conf");
+        fs::write(&conf, "[options]\nArchitecture = auto\n\n#[testing]\n\
+            [core]\nInclude = /etc/pacman.d/mirrorlist\n\n[extra]\nInclude = /etc/pacman.d/mirrorlist\n").unwrap();
+
+        assert_eq!(configured_repos(&conf).unwrap(), vec!["core", "extra"]);
+    }
+}
@@ -1,68 +1,264 @@
+//! The AUR packages rauri installed, in `~/.config/rauri/packages.toml`.
+//!
+//! Each tracked pkgname has an entry with its pkgbase and what was recorded when it
+//! was last built. The file carries a `version`; files written before it existed only
+//! listed package names and are migrated when read, keeping a copy of the old file the
+//! first time the new format is written.
+
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::aur::Aur;
+use crate::history::now;
+use crate::localdb::InstallReason;
+use crate::paths;
+
+/// Version of the packages.toml layout written by this build.
+const SCHEMA_VERSION: u32 = 2;
+
+/// What rauri knows about one tracked package. Entries migrated from the old format
+/// only have their pkgbase and clone URL until the package is rebuilt.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackedPackage {
+    pub pkgbase: String,
+    /// The version rauri last installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The AUR commit it was built from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Unix timestamp of the first install.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_at: Option<i64>,
+    /// Unix timestamp of the last install or update.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clone_url: Option<String>,
+    /// Installed as requested or as a dependency of another package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<InstallReason>,
+    /// Kept at its installed version by updates.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub pinned: bool,
+    /// Left out of update checks entirely.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ignored: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// A pkgbase that was just built and installed.
+pub struct Build {
+    pub version: String,
+    pub commit: Option<String>,
+    /// None keeps the recorded reason, e.g. for updates.
+    pub reason: Option<InstallReason>,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 struct PackageData {
+    version: u32,
+    /// pkgname → entry.
+    #[serde(default)]
+    packages: BTreeMap<String, TrackedPackage>,
+    /// pkgbase → AUR commit whose build files the user last reviewed.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    reviewed: BTreeMap<String, String>,
+}
+
+impl Default for PackageData {
+    fn default() -> Self {
+        PackageData { version: SCHEMA_VERSION, packages: BTreeMap::new(), reviewed: BTreeMap::new() }
+    }
+}
+
+/// The layout before `version` was added: a list of names, plus the pkgbases of
+/// packages tracked after split packages were supported.
+#[derive(Debug, Default, Deserialize)]
+struct LegacyData {
+    #[serde(default)]
     packages: Vec<String>,
+    #[serde(default)]
+    bases: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    reviewed: BTreeMap<String, String>,
+}
+
+impl From<LegacyData> for PackageData {
+    fn from(legacy: LegacyData) -> Self {
+        let mut packages = BTreeMap::new();
+        for (pkgbase, members) in &legacy.bases {
+            for name in members {
+                packages.insert(name.clone(), TrackedPackage::migrated(pkgbase));
+            }
+        }
+        // Packages tracked before pkgbases were recorded are their own pkgbase.
+        for name in &legacy.packages {
+            packages.entry(name.clone()).or_insert_with(|| TrackedPackage::migrated(name));
+        }
+
+        PackageData { version: SCHEMA_VERSION, packages, reviewed: legacy.reviewed }
+    }
+}
+
+impl TrackedPackage {
+    fn migrated(pkgbase: &str) -> Self {
+        TrackedPackage {
+            pkgbase: pkgbase.to_string(),
+            clone_url: Some(Aur::clone_url(pkgbase)),
+            ..TrackedPackage::default()
+        }
+    }
+}
+
+impl PackageData {
+    /// Parse packages.toml, migrating the old layout. Also returns whether it was old.
+    fn parse(content: &str) -> Result<(Self, bool)> {
+        let table: toml::Table = toml::from_str(content)?;
+        match table.get("version").and_then(|v| v.as_integer()) {
+            None => {
+                let legacy: LegacyData = table.try_into()?;
+                Ok((legacy.into(), true))
+            }
+            Some(version) if version > SCHEMA_VERSION as i64 => {
+                anyhow::bail!("written by a newer rauri (version {}, this one reads up to {})",
+                    version, SCHEMA_VERSION)
+            }
+            Some(_) => Ok((table.try_into()?, false)),
+        }
+    }
 }
 
 impl PackageTracker {
     pub fn tracking_file_path() -> PathBuf {
-        let home = dirs::home_dir().expect("Failed to get home directory");
-        home.join(".config").join("rauri").join("packages.toml")
+        paths::config_dir().join("packages.toml")
     }
 
-    pub fn load() -> Result<HashSet<String>> {
-        let path = Self::tracking_file_path();
-        
+    fn load_data() -> Result<PackageData> {
+        Self::load_data_from(&Self::tracking_file_path())
+    }
+
+    fn load_data_from(path: &Path) -> Result<PackageData> {
         if path.exists() {
-            let content = fs::read_to_string(&path)
+            let content = fs::read_to_string(path)
                 .with_context(|| format!("Failed to read tracking file: {}", path.display()))?;
-            
-            let data: PackageData = toml::from_str(&content)
-                .with_context(|| "Failed to parse tracking file")?;
-            
-            Ok(data.packages.into_iter().collect())
+
+            let (data, _) = PackageData::parse(&content)
+                .with_context(|| format!("Failed to parse tracking file: {}", path.display()))?;
+            Ok(data)
         } else {
-            Ok(HashSet::new())
+            Ok(PackageData::default())
         }
     }
 
-    pub fn save(packages: &HashSet<String>) -> Result<()> {
-        let path = Self::tracking_file_path();
+    fn save_data(path: &Path, data: &mut PackageData) -> Result<()> {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create config directory: {}", parent.display()))?;
         }
-        
-        let mut packages_list: Vec<String> = packages.iter().cloned().collect();
-        packages_list.sort();
-        
-        let data = PackageData {
-            packages: packages_list,
-        };
-        
-        let content = toml::to_string_pretty(&data)
+
+        // Keep the file in the old layout around in case this rauri is downgraded.
+        if let Ok(old) = fs::read_to_string(path) {
+            if matches!(PackageData::parse(&old), Ok((_, true))) {
+                let backup = path.with_extension("toml.v1");
+                fs::write(&backup, old)
+                    .with_context(|| format!("Failed to back up tracking file to {}", backup.display()))?;
+            }
+        }
+
+        data.version = SCHEMA_VERSION;
+        let content = toml::to_string_pretty(data)
             .context("Failed to serialize tracking data")?;
-        fs::write(&path, content)
+        fs::write(path, content)
             .with_context(|| format!("Failed to write tracking file: {}", path.display()))?;
-        
+
         Ok(())
     }
 
-    pub fn add(package_name: &str) -> Result<()> {
-        let mut packages = Self::load().unwrap_or_default();
-        packages.insert(package_name.to_string());
-        Self::save(&packages)
+    pub fn load() -> Result<HashSet<String>> {
+        Ok(Self::load_data()?.packages.into_keys().collect())
+    }
+
+    /// Every tracked package by pkgname.
+    pub fn entries() -> Result<BTreeMap<String, TrackedPackage>> {
+        Ok(Self::load_data()?.packages)
+    }
+
+    pub fn get(package_name: &str) -> Option<TrackedPackage> {
+        Self::load_data().ok()?.packages.remove(package_name)
+    }
+
+    /// Map of pkgbase → tracked pkgnames.
+    pub fn load_bases() -> Result<BTreeMap<String, Vec<String>>> {
+        let mut bases: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (name, entry) in Self::load_data()?.packages {
+            bases.entry(entry.pkgbase).or_default().push(name);
+        }
+        Ok(bases)
+    }
+
+    /// Load the tracking file at `path`, change it and write it back. A file that can't
+    /// be read is never overwritten, so tracked packages aren't lost.
+    fn update(path: &Path, change: impl FnOnce(&mut PackageData) -> bool) -> Result<()> {
+        let mut data = Self::load_data_from(path)?;
+        if !change(&mut data) {
+            return Ok(());
+        }
+        Self::save_data(path, &mut data)
+    }
+
+    /// Track `pkgnames` as installed members of `pkgbase`, in addition to any
+    /// members that are already tracked.
+    pub fn add_base(pkgbase: &str, pkgnames: &[String], build: &Build) -> Result<()> {
+        Self::add_base_at(&Self::tracking_file_path(), pkgbase, pkgnames, build)
+    }
+
+    fn add_base_at(path: &Path, pkgbase: &str, pkgnames: &[String], build: &Build) -> Result<()> {
+        let now = now();
+        Self::update(path, |data| {
+            for name in pkgnames {
+                let entry = data.packages.entry(name.clone()).or_default();
+                entry.pkgbase = pkgbase.to_string();
+                entry.version = Some(build.version.clone());
+                entry.commit = build.commit.clone();
+                entry.installed_at = entry.installed_at.or(Some(now));
+                entry.updated_at = Some(now);
+                entry.clone_url = Some(Aur::clone_url(pkgbase));
+                if build.reason.is_some() {
+                    entry.reason = build.reason;
+                }
+            }
+            true
+        })
+    }
+
+    /// Track packages with entries made elsewhere, replacing any existing ones.
+    pub fn insert(entries: impl IntoIterator<Item = (String, TrackedPackage)>) -> Result<()> {
+        Self::update(&Self::tracking_file_path(), |data| {
+            data.packages.extend(entries);
+            true
+        })
     }
 
     pub fn remove(package_name: &str) -> Result<()> {
-        let mut packages = Self::load().unwrap_or_default();
-        packages.remove(package_name);
-        Self::save(&packages)
+        Self::update(&Self::tracking_file_path(), |data| data.packages.remove(package_name).is_some())
+    }
+
+    pub fn reviewed_commit(pkgbase: &str) -> Option<String> {
+        Self::load_data().ok()?.reviewed.remove(pkgbase)
+    }
+
+    pub fn set_reviewed(pkgbase: &str, commit: &str) -> Result<()> {
+        Self::update(&Self::tracking_file_path(), |data| {
+            data.reviewed.insert(pkgbase.to_string(), commit.to_string());
+            true
+        })
     }
 
     #[allow(dead_code)]
@@ -73,3 +269,71 @@ impl PackageTracker {
 
 pub struct PackageTracker;
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_legacy_list() {
+        let (data, migrated) = PackageData::parse("packages = [\"bar\", \"foo\", \"foo-docs\"]\n\n\
+            [bases]\nfoo = [\"foo\", \"foo-docs\"]\n\n[reviewed]\nfoo = \"abc123\"\n").unwrap();
+
+        assert!(migrated);
+        assert_eq!(data.version, SCHEMA_VERSION);
+        assert_eq!(data.packages.keys().collect::<Vec<_>>(), vec!["bar", "foo", "foo-docs"]);
+        assert_eq!(data.packages["foo-docs"], TrackedPackage {
+            pkgbase: "foo".to_string(),
+            clone_url: Some("https://aur.archlinux.org/foo.git".to_string()),
+            ..TrackedPackage::default()
+        });
+        assert_eq!(data.packages["bar"].pkgbase, "bar");
+        assert_eq!(data.reviewed["foo"], "abc123");
+    }
+
+    #[test]
+    fn round_trips_current_format() {
+        let mut data = PackageData::default();
+        data.packages.insert("foo".to_string(), TrackedPackage {
+            pkgbase: "foo".to_string(),
+            version: Some("1.0-1".to_string()),
+            commit: Some("abc123".to_string()),
+            installed_at: Some(1700000000),
+            updated_at: Some(1700000100),
+            clone_url: Some("https://aur.archlinux.org/foo.git".to_string()),
+            reason: Some(InstallReason::Dependency),
+            pinned: true,
+            ignored: false,
+        });
+
+        let content = toml::to_string_pretty(&data).unwrap();
+        assert!(content.starts_with("version = 2\n"));
+        assert!(content.contains("reason = \"dependency\"\n"));
+        assert!(!content.contains("ignored"));
+
+        let (parsed, migrated) = PackageData::parse(&content).unwrap();
+        assert!(!migrated);
+        assert_eq!(parsed.packages, data.packages);
+
+        assert!(PackageData::parse("version = 3\n").is_err());
+    }
+
+    #[test]
+    fn never_overwrites_unreadable_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("packages.toml");
+        let build = Build { version: "1.0-1".to_string(), commit: None, reason: None };
+
+        let newer = "version = 3\n\n[packages.bar]\npkgbase = \"bar\"\n";
+        fs::write(&path, newer).unwrap();
+        assert!(PackageTracker::add_base_at(&path, "foo", &["foo".to_string()], &build).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+
+        fs::write(&path, "packages = [").unwrap();
+        assert!(PackageTracker::add_base_at(&path, "foo", &["foo".to_string()], &build).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "packages = [");
+
+        let missing = tmp.path().join("new").join("packages.toml");
+        PackageTracker::add_base_at(&missing, "foo", &["foo".to_string()], &build).unwrap();
+        assert_eq!(PackageTracker::load_data_from(&missing).unwrap().packages["foo"].version.as_deref(), Some("1.0-1"));
+    }
+}
@@ -1,5 +1,11 @@
 use colored::*;
 use atty::Stream;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set by --json: messages become JSON objects on stderr so stdout only carries the result.
+static JSON_MESSAGES: AtomicBool = AtomicBool::new(false);
+// Set while printing completion candidates, where any message would become a candidate.
+static QUIET: AtomicBool = AtomicBool::new(false);
 
 pub struct Colors;
 
@@ -26,7 +32,32 @@ impl Ui {
         atty::is(Stream::Stdout)
     }
 
+    pub fn set_json(json: bool) {
+        JSON_MESSAGES.store(json, Ordering::Relaxed);
+    }
+
+    /// Drop success, info and warning messages.
+    pub fn set_quiet(quiet: bool) {
+        QUIET.store(quiet, Ordering::Relaxed);
+    }
+
+    /// Write `msg` as `{"level": ..., "message": ...}` to stderr if --json is active.
+    /// Also true, without writing anything, for messages dropped by `set_quiet`.
+    fn json_message(level: &str, msg: &str) -> bool {
+        if level != "error" && QUIET.load(Ordering::Relaxed) {
+            return true;
+        }
+        if !JSON_MESSAGES.load(Ordering::Relaxed) {
+            return false;
+        }
+        eprintln!("{}", serde_json::json!({ "level": level, "message": msg }));
+        true
+    }
+
     pub fn success(msg: &str) {
+        if Self::json_message("success", msg) {
+            return;
+        }
         if Self::is_tty() {
             println!("{} {}", "✓".bright_green(), msg.green());
         } else {
@@ -35,6 +66,9 @@ impl Ui {
     }
 
     pub fn error(msg: &str) {
+        if Self::json_message("error", msg) {
+            return;
+        }
         if Self::is_tty() {
             eprintln!("{} {}", "✗".bright_red(), msg.red());
         } else {
@@ -43,6 +77,9 @@ impl Ui {
     }
 
     pub fn warning(msg: &str) {
+        if Self::json_message("warning", msg) {
+            return;
+        }
         if Self::is_tty() {
             println!("{} {}", "⚠".bright_yellow(), msg.yellow());
         } else {
@@ -51,6 +88,9 @@ impl Ui {
     }
 
     pub fn info(msg: &str) {
+        if Self::json_message("info", msg) {
+            return;
+        }
         if Self::is_tty() {
             println!("{} {}", "ℹ".bright_cyan(), msg.cyan());
         } else {
@@ -58,129 +98,135 @@ impl Ui {
         }
     }
 
-    pub fn print_help(config: &crate::config::Config) {
-        let is_tty = Self::is_tty();
-
-        println!();
-
-        let current_path = config.download_dir.display().to_string();
+    /// Print `msg` and read one line from stdin, trimmed.
+    pub fn prompt(msg: &str) -> anyhow::Result<String> {
+        use std::io::Write;
 
-        if is_tty {
-            println!("{}", "Usage:".bold());
+        if Self::is_tty() {
+            print!("{} {} ", "::".bright_blue().bold(), msg.bold());
         } else {
-            println!("Usage:");
+            print!(":: {} ", msg);
         }
-        println!("  rauri [OPTIONS] [COMMAND] [ARGS]\n");
+        std::io::stdout().flush()?;
 
-        if is_tty {
-            println!("{}", "Options:".bold());
-            println!("  {}  Clear AUR download directory before command", "-C".yellow());
-            println!("  {}  Set AUR download directory path", "-P <path>".yellow());
-        } else {
-            println!("Options:");
-            println!("  -C          Clear AUR download directory before command");
-            println!("  -P <path>   Set AUR download directory path");
-        }
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        Ok(input.trim().to_string())
+    }
 
-        if is_tty {
-            println!("\n{}", "Current AUR Path:".bold());
-            println!("  {}", current_path.green());
-        } else {
-            println!("\nCurrent AUR Path:");
-            println!("  {}", current_path);
-        }
-        println!();
-
-        let s = &config.cmd_search;
-        let sa = format!("{}A", s);
-        let i = &config.cmd_install;
-        let u = &config.cmd_update_all;
-        let r = &config.cmd_remove;
-        let l = &config.cmd_list;
-        let la = format!("{}A", l);
-        let m = &config.cmd_update_mirrors;
-        let lim = config.search_limit;
-
-        if is_tty {
-            println!("{}", "Commands:".bold());
-            println!("  {}  Search packages (top {} per repo)",
-                format!("{s}, --search <pkg>").yellow(), lim);
-            println!("  {}  Search packages (show all results)",
-                format!("{sa}, --search-all <pkg>").yellow());
-            println!("  {}  Install package (AUR or official)",
-                format!("{i}, --install <pkg>").yellow());
-            println!("  {}  Update AUR packages only",
-                format!("{i}, --update-aur").yellow());
-            println!("  {}  Update whole system (pacman -Syy then -Syu, then AUR)",
-                format!("{u}, --update-all").yellow());
-            println!("  {}  Update official packages only (skip AUR)",
-                format!("{u} --skip-aur, --update-all --skip-aur").yellow());
-            println!("  {}  Update mirrorlist with reflector",
-                format!("{m}, --update-mirrors").yellow());
-            println!("  {}  Remove package (also removes package folder)",
-                format!("{r}, --remove <pkg>").yellow());
-            println!("  {}  List AUR packages installed via rauri",
-                format!("{l}, --list").yellow());
-            println!("  {}  List all installed system packages",
-                format!("{la}, --list-all").yellow());
-            println!("  {}  Install from AUR git link",
-                "<AUR_URL>".yellow());
-        } else {
-            println!("Commands:");
-            println!("  {s}, --search <pkg>                   Search packages (top {lim} per repo)");
-            println!("  {sa}, --search-all <pkg>               Search packages (show all results)");
-            println!("  {i}, --install <pkg>                  Install package (AUR or official)");
-            println!("  {i}, --update-aur                     Update AUR packages only");
-            println!("  {u}, --update-all                     Update whole system (pacman -Syy then -Syu, then AUR)");
-            println!("  {u} --skip-aur, --update-all --skip-aur  Update official packages only");
-            println!("  {m}, --update-mirrors                 Update mirrorlist with reflector");
-            println!("  {r}, --remove <pkg>                   Remove package");
-            println!("  {l}, --list                           List AUR packages installed via rauri");
-            println!("  {la}, --list-all                       List all installed system packages");
-            println!("  <AUR_URL>                             Install from AUR git link");
+    /// Parse a selection of numbered entries such as "1 3-5 ^4" into 0-based indices,
+    /// in ascending order. Entries are separated by spaces or commas; `^N` and `^N-M`
+    /// exclude entries, and a selection made only of exclusions (or nothing) starts from all
+    /// `count` entries.
+    pub fn parse_selection(input: &str, count: usize) -> anyhow::Result<Vec<usize>> {
+        let parse_range = |token: &str| -> anyhow::Result<(usize, usize)> {
+            let (start, end) = token.split_once('-').unwrap_or((token, token));
+            let number = |n: &str| n.trim().parse::<usize>().ok().filter(|n| (1..=count).contains(n));
+            match (number(start), number(end)) {
+                (Some(start), Some(end)) if start <= end => Ok((start - 1, end - 1)),
+                _ => anyhow::bail!("Invalid selection: {}", token),
+            }
+        };
+
+        let mut included = vec![false; count];
+        let mut excluded = vec![false; count];
+        let mut any_included = false;
+
+        for token in input.split([' ', ',']).filter(|t| !t.is_empty()) {
+            let (token, marks) = match token.strip_prefix('^') {
+                Some(rest) => (rest, &mut excluded),
+                None => {
+                    any_included = true;
+                    (token, &mut included)
+                }
+            };
+            let (start, end) = parse_range(token)?;
+            marks[start..=end].iter_mut().for_each(|m| *m = true);
         }
 
-        if is_tty {
-            println!("\n{}", "Examples:".bold());
-        } else {
-            println!("\nExamples:");
-        }
-        println!("  rauri {s} package-name");
-        println!("  rauri {sa} package-name");
-        println!("  rauri {i} package-name");
-        println!("  rauri {u}");
-        println!("  rauri {u} --skip-aur");
-        println!("  rauri --update-aur");
-        println!("  rauri {m}");
-        println!("  rauri {r} package-name");
-        println!("  rauri {l}");
-        println!("  rauri {la}");
-        println!("  rauri -C -{l}  # Clear AUR path then list packages");
-        println!("  rauri -P ~/.AUR  # Set AUR path to ~/.AUR");
-        println!("  rauri https://aur.archlinux.org/package-name.git");
-
-        let config_path = crate::config::Config::config_path();
-        let display_path = config_path.canonicalize().unwrap_or(config_path);
-        if is_tty {
-            println!("\n{}", format!("Configuration: {}", display_path.display()).dimmed());
-        } else {
-            println!("\nConfiguration: {}", display_path.display());
-        }
+        Ok((0..count)
+            .filter(|&i| (included[i] || !any_included) && !excluded[i])
+            .collect())
     }
 
-    pub fn format_package(name: &str, version: &str, outdated: bool) -> String {
-        if !Self::is_tty() {
-            if outdated {
-                return format!("{} {} (outdated)", name, version);
-            } else {
-                return format!("{} {}", name, version);
-            }
-        }
+    /// Format a Unix timestamp as "2024-05-01 13:37 UTC".
+    pub fn format_date(timestamp: i64) -> String {
+        let days = timestamp.div_euclid(86400);
+        let secs = timestamp.rem_euclid(86400);
+
+        // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, secs / 3600, secs % 3600 / 60)
+    }
 
-        if outdated {
-            format!("{} {} {}", name.bold(), version.yellow(), "(outdated)".yellow())
-        } else {
-            format!("{} {}", name.bold(), version.green())
+    /// Parse "2024-05-01" as the Unix timestamp of its start (UTC).
+    pub fn parse_date(date: &str) -> anyhow::Result<i64> {
+        let parts: Vec<i64> = date.split('-')
+            .map(|p| p.parse())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| anyhow::anyhow!("Invalid date '{}', expected YYYY-MM-DD", date))?;
+        let (year, month, day) = match parts[..] {
+            [year, month @ 1..=12, day @ 1..=31] => (year, month, day),
+            _ => anyhow::bail!("Invalid date '{}', expected YYYY-MM-DD", date),
+        };
+
+        // Days since 1970-01-01 from a civil date, the inverse of format_date.
+        let year = year - i64::from(month <= 2);
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        Ok((era * 146097 + doe - 719468) * 86400)
+    }
+
+    /// Format a size in bytes the way pacman does, e.g. "12.34 MiB".
+    pub fn format_size(bytes: u64) -> String {
+        const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+        let mut size = bytes as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
         }
+        format!("{:.2} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_dates_and_sizes() {
+        assert_eq!(Ui::format_date(0), "1970-01-01 00:00 UTC");
+        assert_eq!(Ui::format_date(951782400), "2000-02-29 00:00 UTC");
+        assert_eq!(Ui::format_date(1714570620), "2024-05-01 13:37 UTC");
+        assert_eq!(Ui::format_size(512), "512.00 B");
+        assert_eq!(Ui::format_size(5 * 1024 * 1024 + 512 * 1024), "5.50 MiB");
+        assert_eq!(Ui::parse_date("2000-02-29").unwrap(), 951782400);
+        assert_eq!(Ui::parse_date("1970-01-01").unwrap(), 0);
+        assert!(Ui::parse_date("2024-13-01").is_err());
+        assert!(Ui::parse_date("yesterday").is_err());
+    }
+
+    #[test]
+    fn parses_selections() {
+        assert_eq!(Ui::parse_selection("1 3-5 ^4", 6).unwrap(), vec![0, 2, 4]);
+        assert_eq!(Ui::parse_selection("2,1,2", 3).unwrap(), vec![0, 1]);
+        assert_eq!(Ui::parse_selection("^2-3", 4).unwrap(), vec![0, 3]);
+        assert_eq!(Ui::parse_selection("", 2).unwrap(), vec![0, 1]);
+        assert!(Ui::parse_selection("0", 3).is_err());
+        assert!(Ui::parse_selection("4", 3).is_err());
+        assert!(Ui::parse_selection("3-1", 3).is_err());
+        assert!(Ui::parse_selection("foo", 3).is_err());
     }
 }
@@ -0,0 +1,209 @@
+//! Package version comparison with the same semantics as pacman's `vercmp`
+//! (libalpm `alpm_pkg_vercmp`). Versions have the form `[epoch:]pkgver[-pkgrel]`.
+
+use std::cmp::Ordering;
+
+/// Split a full version into (epoch, pkgver, pkgrel).
+/// A missing epoch is "0"; a missing pkgrel is None.
+fn parse_evr(evr: &str) -> (&str, &str, Option<&str>) {
+    let digits = evr.bytes().take_while(|b| b.is_ascii_digit()).count();
+
+    let (epoch, rest) = if evr.as_bytes().get(digits) == Some(&b':') {
+        let epoch = &evr[..digits];
+        (if epoch.is_empty() { "0" } else { epoch }, &evr[digits + 1..])
+    } else {
+        ("0", evr)
+    };
+
+    match rest.rfind('-') {
+        Some(pos) => (epoch, &rest[..pos], Some(&rest[pos + 1..])),
+        None => (epoch, rest, None),
+    }
+}
+
+/// Compare two full package versions, e.g. `1:2.0.1-3` and `2.0.2-1`.
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let (epoch1, ver1, rel1) = parse_evr(a);
+    let (epoch2, ver2, rel2) = parse_evr(b);
+
+    rpmvercmp(epoch1, epoch2)
+        .then_with(|| rpmvercmp(ver1, ver2))
+        .then_with(|| match (rel1, rel2) {
+            // A pkgrel only matters if both versions have one.
+            (Some(r1), Some(r2)) => rpmvercmp(r1, r2),
+            _ => Ordering::Equal,
+        })
+}
+
+/// Compare a single version component (no epoch or pkgrel) segment by segment.
+/// Numeric segments compare numerically and are newer than alphabetic ones;
+/// alphabetic segments compare lexically. Separators only count by their length.
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let one = a.as_bytes();
+    let two = b.as_bytes();
+    let (mut i, mut j) = (0, 0);
+
+    while i < one.len() && j < two.len() {
+        let (sep1, sep2) = (i, j);
+        while i < one.len() && !one[i].is_ascii_alphanumeric() {
+            i += 1;
+        }
+        while j < two.len() && !two[j].is_ascii_alphanumeric() {
+            j += 1;
+        }
+
+        if i >= one.len() || j >= two.len() {
+            break;
+        }
+
+        // A longer run of separators means a newer version.
+        if i - sep1 != j - sep2 {
+            return (i - sep1).cmp(&(j - sep2));
+        }
+
+        let (start1, start2) = (i, j);
+        let is_num = one[i].is_ascii_digit();
+        if is_num {
+            while i < one.len() && one[i].is_ascii_digit() {
+                i += 1;
+            }
+            while j < two.len() && two[j].is_ascii_digit() {
+                j += 1;
+            }
+        } else {
+            while i < one.len() && one[i].is_ascii_alphabetic() {
+                i += 1;
+            }
+            while j < two.len() && two[j].is_ascii_alphabetic() {
+                j += 1;
+            }
+        }
+
+        let seg1 = &one[start1..i];
+        let seg2 = &two[start2..j];
+
+        // Segments of different types: numeric beats alphabetic.
+        if seg2.is_empty() {
+            return if is_num { Ordering::Greater } else { Ordering::Less };
+        }
+
+        let ord = if is_num {
+            let n1 = strip_leading_zeros(seg1);
+            let n2 = strip_leading_zeros(seg2);
+            n1.len().cmp(&n2.len()).then_with(|| n1.cmp(n2))
+        } else {
+            seg1.cmp(seg2)
+        };
+
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+
+    let rest1 = &one[i.min(one.len())..];
+    let rest2 = &two[j.min(two.len())..];
+
+    if rest1.is_empty() && rest2.is_empty() {
+        return Ordering::Equal;
+    }
+
+    // A remaining alphabetic segment never beats an empty one ("1.0rc" < "1.0"),
+    // but anything else left over makes that side newer ("1.5.1" > "1.5").
+    let one_alpha = rest1.first().is_some_and(|c| c.is_ascii_alphabetic());
+    let two_alpha = rest2.first().is_some_and(|c| c.is_ascii_alphabetic());
+    if (rest1.is_empty() && !two_alpha) || one_alpha {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+fn strip_leading_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&d| d == b'0').count();
+    &digits[zeros..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Taken from pacman's test/util/vercmptest.sh. Every case is also checked reversed.
+    const CASES: &[(&str, &str, i8)] = &[
+        // all similar length, no pkgrel
+        ("1.5.0", "1.5.0", 0),
+        ("1.5.1", "1.5.0", 1),
+        // mixed length
+        ("1.5.1", "1.5", 1),
+        // with pkgrel, simple
+        ("1.5.0-1", "1.5.0-1", 0),
+        ("1.5.0-1", "1.5.0-2", -1),
+        ("1.5.0-1", "1.5.1-1", -1),
+        ("1.5.0-2", "1.5.1-1", -1),
+        // with pkgrel, mixed lengths
+        ("1.5-1", "1.5.1-1", -1),
+        ("1.5-2", "1.5.1-1", -1),
+        ("1.5-2", "1.5.1-2", -1),
+        // mixed pkgrel inclusion
+        ("1.5", "1.5-1", 0),
+        ("1.5-1", "1.5", 0),
+        ("1.1-1", "1.1", 0),
+        ("1.0-1", "1.1", -1),
+        ("1.1-1", "1.0", 1),
+        // alphanumeric versions
+        ("1.5b-1", "1.5-1", -1),
+        ("1.5b", "1.5", -1),
+        ("1.5b-1", "1.5", -1),
+        ("1.5b", "1.5.1", -1),
+        // from the manpage
+        ("1.0a", "1.0alpha", -1),
+        ("1.0alpha", "1.0b", -1),
+        ("1.0b", "1.0beta", -1),
+        ("1.0beta", "1.0rc", -1),
+        ("1.0rc", "1.0", -1),
+        // going crazy? alpha-dotted versions
+        ("1.5.a", "1.5", 1),
+        ("1.5.b", "1.5.a", 1),
+        ("1.5.1", "1.5.b", 1),
+        // alpha dots and dashes
+        ("1.5.b-1", "1.5.b", 0),
+        ("1.5-1", "1.5.b", -1),
+        // same/similar content, differing separators
+        ("2.0", "2_0", 0),
+        ("2.0_a", "2_0.a", 0),
+        ("2.0a", "2.0.a", -1),
+        ("2___a", "2_a", 1),
+        // epoch included version comparisons
+        ("0:1.0", "0:1.0", 0),
+        ("0:1.0", "0:1.1", -1),
+        ("1:1.0", "0:1.0", 1),
+        ("1:1.0", "0:1.1", 1),
+        ("1:1.0", "2:1.1", -1),
+        // epoch + sometimes present pkgrel
+        ("1:1.0", "0:1.0-1", 1),
+        ("1:1.0-1", "0:1.1-1", 1),
+        // epoch included on one version
+        ("0:1.0", "1.0", 0),
+        ("0:1.0", "1.1", -1),
+        ("0:1.1", "1.0", 1),
+        ("1:1.0", "1.0", 1),
+        ("1:1.0", "1.1", 1),
+        ("1:1.1", "1.1", 1),
+    ];
+
+    #[test]
+    fn pacman_vercmp_cases() {
+        for &(a, b, expected) in CASES {
+            let expected = expected.cmp(&0);
+            assert_eq!(vercmp(a, b), expected, "vercmp({}, {})", a, b);
+            assert_eq!(vercmp(b, a), expected.reverse(), "vercmp({}, {})", b, a);
+        }
+    }
+}